@@ -2,6 +2,7 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
 
 use anchor_spl::associated_token::{self, AssociatedToken};
+use anchor_spl::metadata::Metadata;
 
 use crate::state::*;
 
@@ -11,7 +12,7 @@ pub struct Initialize<'info> {
     #[account(
         init,
         payer = admin,
-        space = 8 + 1 + 1 + 32 + 1 +1+32 ,
+        space = MasterAccount::LEN,
         seeds = [b"master_account"],
         bump
     )]
@@ -32,6 +33,24 @@ pub struct SetFees<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction()]
+pub struct ApplyFees<'info> {
+    // anybody can apply a fee change once its delay has elapsed
+    #[account(mut, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct ApplyPoolFees<'info> {
+    // anybody can apply a pool fee increase once its delay has elapsed
+    #[account(mut)]
+    pub pool_account: Account<'info, PoolAccount>,
+    pub user: Signer<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction()]
 pub struct SetCollector<'info> {
@@ -62,6 +81,8 @@ pub struct CollectFees<'info> {
     )]
     pub master_account_token_ata: Account<'info, TokenAccount>, // User's ATA for input tokens
     pub token_mint: Account<'info, Mint>,
+    /// CHECK: address checked by `utils::reserved_partner_fees`, may not exist yet
+    pub partner_fee_reserve: UncheckedAccount<'info>, // Launch partner fees of the token mint left in place
     #[account(address = anchor_spl::associated_token::ID)]
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Program<'info, token::Token>,
@@ -70,7 +91,73 @@ pub struct CollectFees<'info> {
 
 #[derive(Accounts)]
 #[instruction()]
-pub struct SetAdmin<'info> {
+pub struct CollectAllFees<'info> {
+    //only fee collector struct
+    #[account(mut, has_one = fee_collector, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub fee_collector: Signer<'info>,
+    #[account(mut, seeds = [b"sol_fee_vault"], bump)]
+    pub sol_fee_vault: SystemAccount<'info>, // Holds the protocol's SOL fees
+    /// CHECK: address checked by `utils::reserved_partner_fees`, may not exist yet
+    pub sol_partner_fee_reserve: UncheckedAccount<'info>, // Launch partner SOL fees left in the vault
+    pub token_program: Program<'info, token::Token>,
+    pub system_program: Program<'info, System>,
+    // remaining accounts: (master account ATA, fee collector ATA, partner fee reserve) triples, one per mint
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct DistributeFees<'info> {
+    //only fee collector struct
+    #[account(mut, has_one = fee_collector, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    pub fee_collector: Signer<'info>,
+    #[account(mut, token::authority = master_account)]
+    pub master_account_token_ata: Option<Account<'info, TokenAccount>>, // None distributes the SOL fees
+    #[account(mut, seeds = [b"sol_fee_vault"], bump)]
+    pub sol_fee_vault: SystemAccount<'info>, // Holds the protocol's SOL fees
+    /// CHECK: address checked by `utils::reserved_partner_fees`, may not exist yet
+    pub partner_fee_reserve: UncheckedAccount<'info>, // Launch partner fees of the distributed mint left in place
+    pub token_program: Program<'info, token::Token>,
+    pub system_program: Program<'info, System>,
+    // remaining accounts: one destination per fee recipient, in table order
+}
+
+#[derive(Accounts)]
+#[instruction(token_index: u8)]
+pub struct PayLaunchPartner<'info> {
+    //only fee collector struct
+    #[account(has_one = fee_collector, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub fee_collector: Signer<'info>, // Pays for the partner fee reserve of pools older than the reserves
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(mut, seeds = [b"pool_fee_ledger", pool_account.key().as_ref()], bump)]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>,
+    #[account(mut, token::authority = master_account)]
+    pub master_account_token_ata: Option<Account<'info, TokenAccount>>, // None pays the partner in SOL
+    #[account(mut, seeds = [b"sol_fee_vault"], bump)]
+    pub sol_fee_vault: SystemAccount<'info>, // Holds the protocol's SOL fees
+    #[account(mut, token::authority = pool_account.launch_partner)]
+    pub partner_token_ata: Option<Account<'info, TokenAccount>>, // Partner's account receiving a token payment
+    #[account(mut, address = pool_account.launch_partner)]
+    pub launch_partner: Option<SystemAccount<'info>>, // Partner's wallet receiving a SOL payment
+    #[account(
+        init_if_needed,
+        payer = fee_collector,
+        space = 8 + PartnerFeeReserve::INIT_SPACE,
+        seeds = [b"partner_fee_reserve", pool_account.token_mint(token_index as usize).as_ref()],
+        bump,
+    )]
+    pub partner_fee_reserve: Account<'info, PartnerFeeReserve>, // Released by the amount paid
+    pub token_program: Program<'info, token::Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct ProposeAdmin<'info> {
     //only admin struct
     #[account(mut, has_one = admin, seeds = [b"master_account"] ,bump)]
     pub master_account: Account<'info, MasterAccount>,
@@ -79,6 +166,273 @@ pub struct SetAdmin<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction()]
+pub struct AcceptAdmin<'info> {
+    //only pending admin struct
+    #[account(mut, has_one = pending_admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub pending_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct CancelAdminProposal<'info> {
+    //only admin struct
+    #[account(mut, has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct InitializeCouncil<'info> {
+    //only admin struct
+    #[account(mut, has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + AdminCouncil::INIT_SPACE,
+        seeds = [b"admin_council"],
+        bump
+    )]
+    pub council: Account<'info, AdminCouncil>,
+    #[account(mut)]
+    pub admin: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct CreateCouncilProposal<'info> {
+    //only council member struct
+    #[account(mut, seeds = [b"admin_council"], bump)]
+    pub council: Account<'info, AdminCouncil>,
+    #[account(
+        init,
+        payer = member,
+        space = 8 + CouncilProposal::INIT_SPACE,
+        seeds = [b"council_proposal", council.key().as_ref(), &council.proposal_count.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, CouncilProposal>,
+    #[account(mut)]
+    pub member: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct ApproveCouncilProposal<'info> {
+    //only council member struct
+    #[account(seeds = [b"admin_council"], bump)]
+    pub council: Account<'info, AdminCouncil>,
+    #[account(
+        mut,
+        has_one = council,
+        seeds = [b"council_proposal", council.key().as_ref(), &proposal.index.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, CouncilProposal>,
+    pub member: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct ExecuteCouncilProposal<'info> {
+    //only council member struct
+    #[account(mut, has_one = council, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut, seeds = [b"admin_council"], bump)]
+    pub council: Account<'info, AdminCouncil>,
+    #[account(
+        mut,
+        has_one = council,
+        seeds = [b"council_proposal", council.key().as_ref(), &proposal.index.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, CouncilProposal>,
+    // only needed by the pool, fee tier and creator actions, pass null otherwise
+    #[account(mut)]
+    pub pool_account: Option<Account<'info, PoolAccount>>,
+    #[account(
+        init_if_needed,
+        payer = member,
+        space = 8 + FeeTier::INIT_SPACE,
+        seeds = [b"fee_tier", proposal.action.creator_seed()],
+        bump
+    )]
+    pub fee_tier: Option<Account<'info, FeeTier>>,
+    #[account(
+        init_if_needed,
+        payer = member,
+        space = 8 + ApprovedCreator::INIT_SPACE,
+        seeds = [b"approved_creator", proposal.action.creator_seed()],
+        bump
+    )]
+    pub approved_creator: Option<Account<'info, ApprovedCreator>>,
+    #[account(mut)]
+    pub member: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct SetPoolFees<'info> {
+    //only admin struct
+    #[account(has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub pool_account: Account<'info, PoolAccount>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(creator: Pubkey)]
+pub struct SetFeeTier<'info> {
+    //only admin struct
+    #[account(has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + FeeTier::INIT_SPACE,
+        seeds = [b"fee_tier", creator.as_ref()],
+        bump
+    )]
+    pub fee_tier: Account<'info, FeeTier>,
+    #[account(mut)]
+    pub admin: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct RemoveFeeTier<'info> {
+    //only admin struct
+    #[account(has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"fee_tier", fee_tier.creator.as_ref()],
+        bump
+    )]
+    pub fee_tier: Account<'info, FeeTier>,
+    #[account(mut)]
+    pub admin: Signer<'info>, // Receives the rent back
+}
+
+#[derive(Accounts)]
+#[instruction(creator: Pubkey)]
+pub struct ApproveCreator<'info> {
+    //only admin struct
+    #[account(has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + ApprovedCreator::INIT_SPACE,
+        seeds = [b"approved_creator", creator.as_ref()],
+        bump
+    )]
+    pub approved_creator: Account<'info, ApprovedCreator>,
+    #[account(mut)]
+    pub admin: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct RevokeCreator<'info> {
+    //only admin struct
+    #[account(has_one = admin, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"approved_creator", approved_creator.creator.as_ref()],
+        bump
+    )]
+    pub approved_creator: Account<'info, ApprovedCreator>,
+    #[account(mut)]
+    pub admin: Signer<'info>, // Receives the rent back
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct ViewPoolFeeLedger<'info> {
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(seeds = [b"pool_fee_ledger", pool_account.key().as_ref()], bump)]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>,
+}
+
+#[derive(Accounts)]
+pub struct ViewPoolStatus<'info> {
+    pub pool_account: Account<'info, PoolAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct RegisterReferrer<'info> {
+    #[account(mut)]
+    pub referrer: Signer<'info>, // This is the payer
+    pub token_mint: Account<'info, Mint>, // Mint the referrer earns its share in, the input token of the pools
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + ReferralAccount::INIT_SPACE,
+        seeds = [b"referral", referrer.key().as_ref(), token_mint.key().as_ref()],
+        bump
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct SetProtocolPause<'info> {
+    //only admin or guardian struct
+    #[account(mut, seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct MigrateMasterAccount<'info> {
+    /// CHECK: Deserialized with its legacy layout in the handler
+    #[account(mut, seeds = [b"master_account"] ,bump)]
+    pub master_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_index: u64)]
+pub struct MigratePoolAccount<'info> {
+    pub input_token_mint: Account<'info, Mint>,
+    /// CHECK: Deserialized with its legacy layout in the handler
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_index_seed(pool_index).as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>, // This is the payer
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction()]
 pub struct CalculateInvariant<'info> {
@@ -95,6 +449,17 @@ pub struct InitializePool<'info> {
     pub output_token_mint: Account<'info, Mint>,
     #[account(mut, seeds = [b"master_account"] ,bump)]
     pub master_account: Account<'info, MasterAccount>,
+    #[account(seeds = [b"fee_tier", user.key().as_ref()], bump)]
+    pub fee_tier: Option<Account<'info, FeeTier>>, // Partner fee tier of the creator, if any
+    #[account(seeds = [b"approved_creator", user.key().as_ref()], bump)]
+    pub approved_creator: Option<Account<'info, ApprovedCreator>>, // Allowlist entry of the creator, if any
+    pub creation_fee_mint: Option<Account<'info, Mint>>, // Only when the creation fee is paid in an SPL token
+    #[account(mut, token::authority = user)]
+    pub user_creation_fee_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, token::authority = master_account)]
+    pub master_account_creation_fee_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"sol_fee_vault"], bump)]
+    pub sol_fee_vault: SystemAccount<'info>, // Holds the protocol's SOL fees
     #[account(init_if_needed,
         payer = user,
         associated_token::mint = input_token_mint,
@@ -107,11 +472,24 @@ pub struct InitializePool<'info> {
         associated_token::authority = master_account,
     )]
     pub master_account_output_fee_ata: Account<'info, TokenAccount>, // User's ATA for input tokens
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + CreatorAccount::INIT_SPACE,
+        seeds = [b"creator_account", user.key().as_ref()],
+        bump,
+    )]
+    pub creator_account: Account<'info, CreatorAccount>, // Counts the creator's pools, numbering the new one
     #[account(
         init,
         payer = user,
-        space = 8 + (1 + 3 * 32 + 2 * 8 + 2 * 16 + 2 * 1 + 2 * 1 + 8 + 1  + 1 + 1 + 32 + 32 + 2 +1 ), // Adjusted space calculation
-        seeds = [b"pool_account", user.key().as_ref(), input_token_mint.key().as_ref()],
+        space = PoolAccount::LEN,
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            (creator_account.pool_count + 1).to_le_bytes().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>,
@@ -124,6 +502,14 @@ pub struct InitializePool<'info> {
         bump,
     )]
     pub bp_token_mint: Account<'info, Mint>, // This account is being created in the transaction
+    #[account(
+        init,
+        payer = user,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>,
     #[account(address = anchor_spl::associated_token::ID)]
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Program<'info, token::Token>,
@@ -135,11 +521,12 @@ pub struct InitializePoolParams {
     pub account_type: u8,
     pub start_timestamp: i64, // timestamp in seconds
     pub end_timestamp: i64,
-    pub start_weights: [u8; 2],
-    pub end_weights: [u8; 2],
+    pub start_weights: [u64; 2], // 1e18 fixed-point, WEIGHT_ONE being 100%
+    pub end_weights: [u64; 2],
     pub is_sol: bool,
     pub is_vesting: bool,
     pub is_buy_only: bool,
+    pub weight_curve: WeightCurve, // shape of the move from start to end weights
 }
 
 #[derive(Accounts)]
@@ -150,33 +537,176 @@ pub struct PausePool<'info> {
     #[account(
         mut,
         has_one = owner,
-        seeds = [b"pool_account", owner.key().as_ref(), input_token_mint.key().as_ref()],
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>, // This is the payer
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct UnPausePool<'info> {
+    //only owner struct
+    pub input_token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>, // This is the payer
+}
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct AddPoolToken<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>, // user is owner
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    pub token_mint: Account<'info, Mint>, // Mint of the token added to the pool
+    #[account(init_if_needed,
+        payer = user,
+        associated_token::mint = token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_token_ata: Account<'info, TokenAccount>, // Pool's ATA for the added token
+    #[account(init_if_needed,
+        payer = user,
+        associated_token::mint = token_mint,
+        associated_token::authority = master_account)]
+    pub master_account_fee_ata: Account<'info, TokenAccount>, // Master account's ATA receiving fees in the added token
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>, // Created here for pools older than the ledger
+    #[account(address = anchor_spl::associated_token::ID)]
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPoolMetadata<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>, // user is owner
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    #[account(
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PoolMetadata::INIT_SPACE,
+        seeds = [b"pool_metadata", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_metadata: Account<'info, PoolMetadata>,
+    #[account(
+        seeds = [b"bp_token_mint", pool_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub bp_token_mint: Account<'info, Mint>,
+    /// CHECK: Token metadata of the BP token mint, created or updated by the token metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), bp_token_mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub bp_token_metadata: UncheckedAccount<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetWeightSchedule<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>, // user is owner
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>,
-    #[account(mut)]
-    pub owner: Signer<'info>, // This is the payer
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + WeightSchedule::INIT_SPACE,
+        seeds = [b"weight_schedule", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub weight_schedule: Account<'info, WeightSchedule>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction()]
-pub struct UnPausePool<'info> {
-    //only owner struct
-    pub input_token_mint: Account<'info, Mint>,
+pub struct UpdateWeightsGradually<'info> {
+    pub user: Signer<'info>, // user is owner
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
     #[account(
         mut,
-        seeds = [b"pool_account", user.key().as_ref(), input_token_mint.key().as_ref()],
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>,
-    #[account(mut)]
-    pub user: Signer<'info>, // This is the payer
+    #[account(seeds = [b"weight_schedule", pool_account.key().as_ref()], bump)]
+    pub weight_schedule: Option<Account<'info, WeightSchedule>>, // Required when the pool has a weight schedule
 }
 
 #[derive(Accounts)]
 pub struct InitializePoolFunds<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
     /// CHECK: Just used to derive account pda
     pub input_token_mint: Account<'info, Mint>,
     pub output_token_mint: Account<'info, Mint>,
@@ -188,7 +718,12 @@ pub struct InitializePoolFunds<'info> {
     pub bp_token_mint: Account<'info, Mint>,
     #[account(
         mut,
-        seeds = [b"pool_account", user.key().as_ref(), input_token_mint.key().as_ref()],
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>,
@@ -228,12 +763,15 @@ pub struct InitializePoolFunds<'info> {
 pub struct InitializePoolFundsParams {
     pub normalized_weights: [u8; 2],
     pub balances: [u64; 2],
+    pub additional_balances: Vec<u64>, // balances of the tokens added with `add_pool_token`, in order
 }
 
 #[derive(Accounts)]
 pub struct JoinPool<'info> {
     #[account(mut)]
     pub user: Signer<'info>, // user is owner
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
     pub output_token_mint: Account<'info, Mint>,
     #[account(mut,
         associated_token::mint = output_token_mint,
@@ -247,10 +785,17 @@ pub struct JoinPool<'info> {
     pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
     #[account(
         mut,
-        seeds = [b"pool_account", user.key().as_ref(), input_token_mint.key().as_ref()],
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>, // The pool account
+    #[account(seeds = [b"weight_schedule", pool_account.key().as_ref()], bump)]
+    pub weight_schedule: Option<Account<'info, WeightSchedule>>, // Required once the pool has a weight schedule
     #[account(mut,
         associated_token::mint = input_token_mint,
         associated_token::authority = pool_account)]
@@ -275,6 +820,40 @@ pub struct JoinPool<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct JoinPoolMulti<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>, // user is owner
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut,
+        associated_token::mint = bp_token_mint,
+        associated_token::authority = user)]
+    pub user_bp_ata: Account<'info, TokenAccount>, // User's ATA for BP tokens
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>, // The pool account
+    #[account(seeds = [b"weight_schedule", pool_account.key().as_ref()], bump)]
+    pub weight_schedule: Option<Account<'info, WeightSchedule>>, // Required once the pool has a weight schedule
+    #[account(
+        mut,
+        seeds = [b"bp_token_mint", pool_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub bp_token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    // remaining accounts: for each pool token, the ATAs of the user and the pool
+}
+
 #[derive(Accounts)]
 pub struct Swap<'info> {
     /// CHECK: Just used to derive account pda
@@ -300,10 +879,17 @@ pub struct Swap<'info> {
     pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
     #[account(
         mut,
-        seeds = [b"pool_account", owner.key().as_ref(), input_token_mint.key().as_ref()],
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>, // The pool account
+    #[account(seeds = [b"weight_schedule", pool_account.key().as_ref()], bump)]
+    pub weight_schedule: Option<Account<'info, WeightSchedule>>, // Required once the pool has a weight schedule
     #[account(mut,
         associated_token::mint = input_token_mint,
         associated_token::authority = pool_account)]
@@ -320,12 +906,101 @@ pub struct Swap<'info> {
         associated_token::mint = input_token_mint,
         associated_token::authority = master_account)]
     pub fee_collector_input_ata: Account<'info, TokenAccount>, // Pool's ATA for BP tokens, holding the total supply of BP tokens
+    #[account(mut,
+        seeds = [b"referral", referral_account.referrer.as_ref(), input_token_mint.key().as_ref()],
+        bump)]
+    pub referral_account: Option<Account<'info, ReferralAccount>>, // Referrer credited for this swap, if any
+    #[account(mut, token::mint = input_token_mint)]
+    pub referrer_input_ata: Option<Account<'info, TokenAccount>>, // Referrer's account receiving its share of the swap fee
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>, // Created here for pools older than the ledger
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PartnerFeeReserve::INIT_SPACE,
+        seeds = [b"partner_fee_reserve", input_token_mint.key().as_ref()],
+        bump,
+    )]
+    pub partner_fee_reserve: Option<Account<'info, PartnerFeeReserve>>, // Required once the pool has a launch partner
     pub bp_token_mint: Account<'info, Mint>, // The BP token mint (might not be needed for transfer)
     pub token_program: Program<'info, Token>,
 
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct SwapTokens<'info> {
+    /// CHECK: Just used to derive account pda
+    pub owner: UncheckedAccount<'info>,
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>, // The pool account
+    #[account(seeds = [b"weight_schedule", pool_account.key().as_ref()], bump)]
+    pub weight_schedule: Option<Account<'info, WeightSchedule>>, // Required once the pool has a weight schedule
+    pub token_in_mint: Account<'info, Mint>, // Mint of the token paid by the user
+    pub token_out_mint: Account<'info, Mint>, // Mint of the token received by the user
+    #[account(mut,
+        associated_token::mint = token_in_mint,
+        associated_token::authority = user)]
+    pub user_token_in_ata: Account<'info, TokenAccount>,
+    #[account(init_if_needed,
+        payer = user,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = user)]
+    pub user_token_out_ata: Account<'info, TokenAccount>,
+    #[account(mut,
+        associated_token::mint = token_in_mint,
+        associated_token::authority = pool_account)]
+    pub pool_token_in_ata: Account<'info, TokenAccount>,
+    #[account(mut,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = pool_account)]
+    pub pool_token_out_ata: Account<'info, TokenAccount>,
+    #[account(mut,
+        associated_token::mint = token_out_mint,
+        associated_token::authority = master_account)]
+    pub fee_collector_out_ata: Account<'info, TokenAccount>, // Master account's ATA receiving the swap fee
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>, // Created here for pools older than the ledger
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PartnerFeeReserve::INIT_SPACE,
+        seeds = [b"partner_fee_reserve", token_out_mint.key().as_ref()],
+        bump,
+    )]
+    pub partner_fee_reserve: Option<Account<'info, PartnerFeeReserve>>, // Required once the pool has a launch partner
+    #[account(address = anchor_spl::associated_token::ID)]
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct Redeem<'info> {
     /// CHECK: Just used to derive account pda
@@ -354,7 +1029,12 @@ pub struct Redeem<'info> {
     pub output_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
     #[account(
         mut,
-        seeds = [b"pool_account", owner.key().as_ref(), input_token_mint.key().as_ref()],
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
         bump,
     )]
     pub pool_account: Account<'info, PoolAccount>, // The pool account
@@ -376,10 +1056,265 @@ pub struct Redeem<'info> {
         associated_token::mint = output_token_mint,
         associated_token::authority = master_account)]
     pub fee_collector_output_ata: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"sol_fee_vault"], bump)]
+    pub sol_fee_vault: SystemAccount<'info>, // Holds the protocol's SOL fees
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>, // Created here for pools older than the ledger
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PartnerFeeReserve::INIT_SPACE,
+        seeds = [b"partner_fee_reserve", pool_account.output_token_mint.as_ref()],
+        bump,
+    )]
+    pub partner_fee_reserve: Option<Account<'info, PartnerFeeReserve>>, // Required once the pool has a launch partner
+    #[account(address = anchor_spl::associated_token::ID)]
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExitPoolMulti<'info> {
+    /// CHECK: Just used to derive account pda
+    pub owner: UncheckedAccount<'info>,
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut,
+        associated_token::mint = bp_token_mint,
+        associated_token::authority = user)]
+    pub user_bp_ata: Account<'info, TokenAccount>, // User's ATA for BP tokens
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>, // The pool account
+    #[account(
+        mut,
+        seeds = [b"bp_token_mint", pool_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub bp_token_mint: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>, // Created here for pools older than the ledger
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    // remaining accounts: for each pool token, the ATAs of the user, the pool and the master account,
+    // followed by the partner fee reserve of each pool token once the pool has a launch partner
+}
+
+#[derive(Accounts)]
+pub struct OwnerExit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(seeds = [b"master_account"] ,bump)]
+    pub master_account: Account<'info, MasterAccount>,
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    pub output_token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(
+        mut,
+        seeds = [b"bp_token_mint", pool_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub bp_token_mint: Account<'info, Mint>,
+    #[account(mut,
+        associated_token::mint = bp_token_mint,
+        associated_token::authority = owner)]
+    pub user_bp_ata: Account<'info, TokenAccount>, // Owner's ATA for BP tokens, burnt in full
+    #[account(mut,
+        associated_token::mint = input_token_mint,
+        associated_token::authority = owner)]
+    pub user_input_ata: Account<'info, TokenAccount>, // Owner's ATA for input tokens
+    #[account(init_if_needed,
+        payer = owner,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = owner)]
+    pub user_output_ata: Account<'info, TokenAccount>, // Owner's ATA for output tokens
+    #[account(mut,
+        associated_token::mint = input_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_input_ata: Account<'info, TokenAccount>, // Pool's ATA for input tokens
+    #[account(mut,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_output_ata: Account<'info, TokenAccount>, // Pool's ATA for output tokens
+    #[account(mut,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = master_account)]
+    pub fee_collector_output_ata: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"sol_fee_vault"], bump)]
+    pub sol_fee_vault: SystemAccount<'info>, // Holds the protocol's SOL fees
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + PoolFeeLedger::INIT_SPACE,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Account<'info, PoolFeeLedger>, // Created here for pools older than the ledger
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + PartnerFeeReserve::INIT_SPACE,
+        seeds = [b"partner_fee_reserve", pool_account.output_token_mint.as_ref()],
+        bump,
+    )]
+    pub partner_fee_reserve: Option<Account<'info, PartnerFeeReserve>>, // Required once the pool has a launch partner
     #[account(address = anchor_spl::associated_token::ID)]
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct CancelPool<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    pub output_token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            owner.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(
+        mut,
+        seeds = [b"bp_token_mint", pool_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub bp_token_mint: Account<'info, Mint>,
+    #[account(mut,
+        associated_token::mint = bp_token_mint,
+        associated_token::authority = owner)]
+    pub user_bp_ata: Account<'info, TokenAccount>, // Owner's ATA for BP tokens, holding the whole supply
+    #[account(mut,
+        associated_token::mint = input_token_mint,
+        associated_token::authority = owner)]
+    pub user_input_ata: Account<'info, TokenAccount>, // Owner's ATA for input tokens
+    #[account(init_if_needed,
+        payer = owner,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = owner)]
+    pub user_output_ata: Account<'info, TokenAccount>, // Owner's ATA for output tokens
+    #[account(mut,
+        associated_token::mint = input_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_input_ata: Account<'info, TokenAccount>, // Pool's ATA for input tokens
+    #[account(mut,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_output_ata: Account<'info, TokenAccount>, // Pool's ATA for output tokens
+    #[account(address = anchor_spl::associated_token::ID)]
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>, // user is owner, receives the swept tokens and the reclaimed rent
+    pub input_token_mint: Account<'info, Mint>, // Mint of the input Token (XYZ) used to dervie the pool account
+    pub output_token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [
+            b"pool_account",
+            user.key().as_ref(),
+            input_token_mint.key().as_ref(),
+            pool_account.pool_index_seed().as_ref(),
+        ],
+        bump,
+    )]
+    pub pool_account: Account<'info, PoolAccount>,
+    #[account(
+        mut,
+        seeds = [b"bp_token_mint", pool_account.to_account_info().key.as_ref()],
+        bump,
+    )]
+    pub bp_token_mint: Account<'info, Mint>,
+    #[account(mut,
+        associated_token::mint = input_token_mint,
+        associated_token::authority = user)]
+    pub user_input_ata: Account<'info, TokenAccount>, // User's ATA for input tokens
+    #[account(init_if_needed,
+        payer = user,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = user)]
+    pub user_output_ata: Account<'info, TokenAccount>, // User's ATA for output tokens
+    #[account(mut,
+        associated_token::mint = input_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_input_ata: Account<'info, TokenAccount>, // Pool's ATA for input tokens
+    #[account(mut,
+        associated_token::mint = output_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_output_ata: Account<'info, TokenAccount>, // Pool's ATA for output tokens
+    #[account(mut,
+        associated_token::mint = bp_token_mint,
+        associated_token::authority = pool_account)]
+    pub pool_bp_ata: Option<Account<'info, TokenAccount>>, // Only exists once the pool has been joined
+    #[account(
+        mut,
+        seeds = [b"pool_fee_ledger", pool_account.key().as_ref()],
+        bump,
+    )]
+    pub pool_fee_ledger: Option<Account<'info, PoolFeeLedger>>, // Absent for pools older than the ledger
+    #[account(
+        mut,
+        seeds = [b"weight_schedule", pool_account.key().as_ref()],
+        bump,
+        close = user,
+    )]
+    pub weight_schedule: Option<Account<'info, WeightSchedule>>, // Only for pools with a weight schedule
+    #[account(
+        mut,
+        seeds = [b"pool_metadata", pool_account.key().as_ref()],
+        bump,
+        close = user,
+    )]
+    pub pool_metadata: Option<Account<'info, PoolMetadata>>, // Only for pools with metadata
+    #[account(address = anchor_spl::associated_token::ID)]
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    // remaining accounts: for each token added with `add_pool_token`, the ATAs of the pool and the user
+}
@@ -1,4 +1,5 @@
 use crate::logExpMath::Decimal;
+use crate::state::WeightCurve;
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 use num::bigint::BigInt;
@@ -15,6 +16,12 @@ construct_uint! {
 #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Decimal(pub U256);
 */
+pub const BPS_DENOMINATOR: u64 = 10_000;
+// Weights and weight change progress are 1e18 fixed-point values, WEIGHT_ONE being 100%
+pub const WEIGHT_ONE: u64 = 1_000_000_000_000_000_000;
+// Shape parameter k of the non-linear weight curves, the higher the more pronounced the curve
+pub const WEIGHT_CURVE_STEEPNESS: u64 = 5;
+
 // Stateless implementation
 pub struct WeightedMath;
 
@@ -33,10 +40,16 @@ pub trait WeightedMathTrait {
         end_time: i64,
     ) -> Result<u64, ProgramError>;
     fn interpolate_weight(
-        start_weight: u8,
-        end_weight: u8,
-        percent_progress: u64,
-    ) -> Result<u8, ProgramError>;
+        start_weight: u64,
+        end_weight: u64,
+        progress: u64,
+    ) -> Result<u64, ProgramError>;
+    fn calculate_curve_progress(
+        curve: WeightCurve,
+        current_time: i64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<u64, ProgramError>;
     fn calc_bpt_out_given_exact_tokens_in(
         balances: &[BigInt],
         normalized_weights: &[BigInt],
@@ -64,12 +77,11 @@ pub trait WeightedMathTrait {
         bpt_total_supply: &BigInt,
         bpt_amount_in: &BigInt,
     ) -> Result<Vec<BigInt>, MathError>;
-    fn calc_due_fee_amount(amount: u64, fee_percentage: u8) -> u64;
+    fn calc_due_fee_amount(amount: u64, fee_bps: u16) -> u64;
     /*  fn calc_token_in_given_exact_bpt_out(balance: u128, normalized_weight: u128, bpt_amount_out: u128, bpt_total_supply: u128, swap_fee_percentage: u128) -> Result<U128, MathError>;
     fn calc_bpt_in_given_exact_tokens_out(balances: &[u128], normalized_weights: &[u128], amounts_out: &[u128], bpt_total_supply: u128, swap_fee_percentage: u128) -> Result<U128, MathError>;
     fn calc_token_out_given_exact_bpt_in(balance: u128, normalized_weight: u128, bpt_amount_in: u128, bpt_total_supply: u128, swap_fee_percentage: u128) -> Result<U128, MathError>;
     fn calc_tokens_out_given_exact_bpt_in(balances: &[u128], bpt_amount_in: u128, total_bpt: u128) -> Result<Vec<u128>, MathError>;
-    fn calc_due_token_protocol_swap_fee_amount(balance: u128, normalized_weight: u128, previous_invariant: u128, current_invariant: u128, protocol_swap_fee_percentage: u128) -> Result<U128, MathError>;
     */
 }
 
@@ -132,67 +144,84 @@ impl WeightedMathTrait for WeightedMath {
 
         Ok(invariant)
     }
+    // Share of the sale elapsed at `current_time`, moving every second, WEIGHT_ONE being the whole sale
     fn calculate_weight_change_progress(
         current_time: i64,
         start_time: i64,
         end_time: i64,
     ) -> Result<u64, ProgramError> {
         if current_time >= end_time {
-            return Ok(1u64);
-        } else if current_time < start_time {
+            return Ok(WEIGHT_ONE);
+        } else if current_time <= start_time {
             return Ok(0u64);
         }
-        /*msg!(
-            "Calculating pct cu{:?},st{:?}-end{:?}",
-            current_time,
-            start_time,
-            end_time
-        );*/
 
-        let total_seconds: i64 = end_time - start_time;
-        let second_elapsed: i64 = current_time - start_time;
+        let total_seconds = (end_time - start_time) as u128;
+        let seconds_elapsed = (current_time - start_time) as u128;
         //safe since total second can't be 0
-        let delta = second_elapsed * 100 / total_seconds;
-        //msg!("delta {:?}", delta);
-
-        let mut bytes = delta.to_le_bytes();
-        let delta_64 = u64::from_le_bytes(bytes);
-
-        Ok(delta_64)
+        let progress = seconds_elapsed * WEIGHT_ONE as u128 / total_seconds;
+        Ok(progress as u64)
     }
-    // returns the current applicable weight
-    // We might need more precision here
+    // returns the current applicable weight, `progress` being a WEIGHT_ONE fraction of the way to `end_weight`
     fn interpolate_weight(
-        start_weight_u8: u8,
-        end_weight_u8: u8,
-        percent_progress: u64,
-    ) -> Result<u8, ProgramError> {
-        let start_weight: u64 = start_weight_u8.try_into().unwrap();
-        let end_weight: u64 = end_weight_u8.try_into().unwrap();
-        //msg!("interpolating {:?},{:?}", start_weight, end_weight);
-
-        let mut interpolation: u64;
-        if percent_progress == 0u64 || start_weight == end_weight {
-            interpolation = start_weight;
-        }
-        if percent_progress >= 100u64 {
-            interpolation = end_weight;
-        }
-
-        if start_weight > end_weight {
-            let weight_delta = percent_progress * (start_weight - end_weight);
-            interpolation = (start_weight * 100 - weight_delta) / 100;
+        start_weight: u64,
+        end_weight: u64,
+        progress: u64,
+    ) -> Result<u64, ProgramError> {
+        let progress = progress.min(WEIGHT_ONE) as u128;
+        let interpolation = if start_weight > end_weight {
+            let weight_delta = (start_weight - end_weight) as u128 * progress / WEIGHT_ONE as u128;
+            start_weight as u128 - weight_delta
         } else {
-            let weight_delta = percent_progress * (end_weight - start_weight);
-            interpolation = (start_weight * 100 + weight_delta) / 100;
+            let weight_delta = (end_weight - start_weight) as u128 * progress / WEIGHT_ONE as u128;
+            start_weight as u128 + weight_delta
+        };
+        Ok(interpolation as u64)
+    }
+    // Share of the weight change reached at `current_time` along `curve`, WEIGHT_ONE being the whole change
+    fn calculate_curve_progress(
+        curve: WeightCurve,
+        current_time: i64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<u64, ProgramError> {
+        let progress =
+            WeightedMath::calculate_weight_change_progress(current_time, start_time, end_time)?;
+        if curve == WeightCurve::Linear || progress == 0 || progress == WEIGHT_ONE {
+            return Ok(progress);
         }
-        /*msg!(
-            "start-weight {:?}, endweight {:?}, interpolataion cu{:?}",
-            start_weight,
-            end_weight,
-            interpolation
-        );*/
-        Ok(interpolation.try_into().unwrap())
+        let one = BigInt::from(WEIGHT_ONE);
+        let progress = BigInt::from(progress);
+        let steepness = BigInt::from(WEIGHT_CURVE_STEEPNESS) * &one;
+
+        let curve_progress = match curve {
+            WeightCurve::Linear => progress,
+            // (e^(k*p) - 1) / (e^k - 1)
+            WeightCurve::Exponential => {
+                let numerator = Decimal::exp(&steepness * &progress / &one)? - &one;
+                let denominator = Decimal::exp(steepness.clone())? - &one;
+                numerator * &one / denominator
+            }
+            // ln(1 + k*p) / ln(1 + k)
+            WeightCurve::Logarithmic => {
+                let numerator = Decimal::ln(&one + &steepness * &progress / &one)?;
+                let denominator = Decimal::ln(&one + &steepness)?;
+                numerator * &one / denominator
+            }
+            // logistic curve 1 / (1 + e^(-k*(p - 1/2))), rescaled to run from 0 to 1
+            WeightCurve::Sigmoid => {
+                let half = &one / BigInt::from(2);
+                let logistic = |x: &BigInt| -> Result<BigInt, MathError> {
+                    let exponent = (&half - x) * &steepness / &one;
+                    Ok(&one * &one / (&one + Decimal::exp(exponent)?))
+                };
+                let low = logistic(&BigInt::from(0))?;
+                let high = logistic(&one)?;
+                (logistic(&progress)? - &low) * &one / (high - low)
+            }
+        };
+        let curve_progress = curve_progress.clamp(BigInt::from(0), one);
+        Ok(u64::try_from(&curve_progress).map_err(|_| MathError::MulOverflow)?)
     }
     fn calc_bpt_out_given_exact_tokens_in(
         balances: &[BigInt],
@@ -285,39 +314,11 @@ impl WeightedMathTrait for WeightedMath {
         }
     }
     // Calculates the due token protocol swap fee amount, using BigInt for calculations and converting to Decimal only for the power operation.
-    fn calc_due_fee_amount(amount: u64, fee_percentage: u8) -> u64 {
-        amount * fee_percentage as u64 / 100u64
+    // Fees are expressed in basis points, 10_000 bps being 100%
+    fn calc_due_fee_amount(amount: u64, fee_bps: u16) -> u64 {
+        (amount as u128 * fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
     }
 
-    /*
-    fn get_due_protocol_fee_amounts(
-        balances: Vec<BigInt>,
-        normalized_weights: Vec<BigInt>,
-        max_weight_token_index: usize,
-        previous_invariant: &BigInt,
-        current_invariant: &BigInt,
-        protocol_swap_fee_percentage: &BigInt,
-    ) -> Result<Vec<BigInt>, MathError> {
-        let total_tokens = balances.len();
-        let mut due_protocol_fee_amounts = vec![BigInt::zero(); total_tokens];
-
-        if protocol_swap_fee_percentage.is_zero() {
-            return Ok(due_protocol_fee_amounts);
-        }
-
-        let fee_amount = calc_due_token_protocol_swap_fee_amount(
-            &balances[max_weight_token_index],
-            &normalized_weights[max_weight_token_index],
-            previous_invariant,
-            current_invariant,
-            protocol_swap_fee_percentage,
-        )?;
-
-        // Assuming that only the token with the max weight index gets its fee calculated and set
-        due_protocol_fee_amounts[max_weight_token_index] = fee_amount;
-
-        Ok(due_protocol_fee_amounts)
-    }*/
     fn calc_out_given_in(
         balance_in: &BigInt,
         weight_in: &BigInt,
@@ -505,24 +506,7 @@ impl WeightedMathTrait for WeightedMath {
          }).collect::<Result<Vec<u128>, MathError>>()
      }
 
-    fn calc_due_token_protocol_swap_fee_amount(balance: u128, normalized_weight: u128, previous_invariant: u128, current_invariant: u128, protocol_swap_fee_percentage: u128) -> Result<U128, MathError> {
-         let balance = U128::from(balance);
-         let normalized_weight = U128::from(normalized_weight);
-         let previous_invariant = U128::from(previous_invariant);
-         let current_invariant = U128::from(current_invariant);
-         let protocol_swap_fee_percentage = U128::from(protocol_swap_fee_percentage);
-
-         if current_invariant <= previous_invariant {
-             return Ok(U128::from(0));
-         }
-
-         let growth_factor = current_invariant.checked_div(previous_invariant).ok_or(MathError::DividedByZero)?;
-         let powered_growth = growth_factor.checked_pow(normalized_weight).ok_or(MathError::DividedByZero)?;
-         let fee_base = U128::from(1).checked_sub(powered_growth).ok_or(MathError::DividedByZero)?;
-         let due_fee = balance.checked_mul(fee_base).ok_or(MathError::DividedByZero)?.checked_mul(protocol_swap_fee_percentage).ok_or(MathError::DividedByZero)?;
-
-         Ok(due_fee)
-     }*/
+    */
 }
 
 #[cfg(test)]
@@ -549,6 +533,109 @@ mod tests {
         assert!(result > U128::from(0), "Invariant should be greater than 0");
     }
 
+    #[test]
+    fn test_calc_due_fee_amount_bps() {
+        // 0.3% and 2.5% are representable
+        assert_eq!(WeightedMath::calc_due_fee_amount(1_000_000, 30), 3_000);
+        assert_eq!(WeightedMath::calc_due_fee_amount(1_000_000, 250), 25_000);
+        // no overflow on large amounts
+        assert_eq!(WeightedMath::calc_due_fee_amount(u64::MAX, 10_000), u64::MAX);
+    }
+
+    #[test]
+    fn test_calculate_weight_change_progress() {
+        // moves every second rather than every percent of the sale
+        assert_eq!(
+            WeightedMath::calculate_weight_change_progress(0, 0, 1_000).unwrap(),
+            0
+        );
+        assert_eq!(
+            WeightedMath::calculate_weight_change_progress(1, 0, 1_000).unwrap(),
+            WEIGHT_ONE / 1_000
+        );
+        assert_eq!(
+            WeightedMath::calculate_weight_change_progress(999, 0, 1_000).unwrap(),
+            WEIGHT_ONE / 1_000 * 999
+        );
+        // stays at the end weights once the sale is over
+        assert_eq!(
+            WeightedMath::calculate_weight_change_progress(1_000, 0, 1_000).unwrap(),
+            WEIGHT_ONE
+        );
+        assert_eq!(
+            WeightedMath::calculate_weight_change_progress(5_000, 0, 1_000).unwrap(),
+            WEIGHT_ONE
+        );
+    }
+
+    #[test]
+    fn test_calculate_curve_progress() {
+        let curves = [
+            WeightCurve::Linear,
+            WeightCurve::Exponential,
+            WeightCurve::Logarithmic,
+            WeightCurve::Sigmoid,
+        ];
+        for curve in curves {
+            // every curve runs from 0 to 1 over the sale and only moves forward
+            assert_eq!(
+                WeightedMath::calculate_curve_progress(curve, 0, 0, 100).unwrap(),
+                0
+            );
+            assert_eq!(
+                WeightedMath::calculate_curve_progress(curve, 100, 0, 100).unwrap(),
+                WEIGHT_ONE
+            );
+            assert_eq!(
+                WeightedMath::calculate_curve_progress(curve, 150, 0, 100).unwrap(),
+                WEIGHT_ONE
+            );
+            let mut previous = 0;
+            for time in 1..=100 {
+                let progress = WeightedMath::calculate_curve_progress(curve, time, 0, 100).unwrap();
+                assert!(progress >= previous);
+                previous = progress;
+            }
+        }
+        let halfway = |curve| WeightedMath::calculate_curve_progress(curve, 50, 0, 100).unwrap();
+        let half = WEIGHT_ONE / 2;
+        assert_eq!(halfway(WeightCurve::Linear), half);
+        // (e^2.5 - 1) / (e^5 - 1) ~ 7.6%
+        assert!(halfway(WeightCurve::Exponential) > WEIGHT_ONE / 1000 * 75);
+        assert!(halfway(WeightCurve::Exponential) < WEIGHT_ONE / 1000 * 77);
+        // ln(3.5) / ln(6) ~ 69.9%
+        assert!(halfway(WeightCurve::Logarithmic) > WEIGHT_ONE / 1000 * 698);
+        assert!(halfway(WeightCurve::Logarithmic) < WEIGHT_ONE / 1000 * 700);
+        // symmetric around the middle of the sale
+        assert!(halfway(WeightCurve::Sigmoid).abs_diff(half) < 10);
+    }
+
+    #[test]
+    fn test_interpolate_weight() {
+        let percent = WEIGHT_ONE / 100;
+        assert_eq!(
+            WeightedMath::interpolate_weight(90 * percent, 10 * percent, 0).unwrap(),
+            90 * percent
+        );
+        assert_eq!(
+            WeightedMath::interpolate_weight(90 * percent, 10 * percent, WEIGHT_ONE).unwrap(),
+            10 * percent
+        );
+        assert_eq!(
+            WeightedMath::interpolate_weight(90 * percent, 10 * percent, WEIGHT_ONE / 4).unwrap(),
+            70 * percent
+        );
+        assert_eq!(
+            WeightedMath::interpolate_weight(10 * percent, 90 * percent, WEIGHT_ONE / 4).unwrap(),
+            30 * percent
+        );
+        // a single second of a day-long sale still moves the weight
+        let progress = WeightedMath::calculate_weight_change_progress(1, 0, 86_400).unwrap();
+        let weight =
+            WeightedMath::interpolate_weight(90 * percent, 10 * percent, progress).unwrap();
+        assert!(weight < 90 * percent && weight > 89 * percent);
+    }
+
     #[test]
     fn test_calculate_invariant_error_handling() {
         let normalized_weights = [0u128]; // Invalid input
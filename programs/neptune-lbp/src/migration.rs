@@ -0,0 +1,232 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+
+use crate::math::WEIGHT_ONE;
+use crate::state::*;
+use crate::NeptuneError;
+
+// MasterAccount layout with whole-percent fees, stored as version 0
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct MasterAccountV0 {
+    pub account_type: u8,
+    pub version: u8,
+    pub admin: Pubkey,
+    pub protocol_swap_fee_percentage: u8,
+    pub protocol_flat_rate_percentage: u8,
+    pub fee_collector: Pubkey,
+}
+
+// PoolAccount layout with whole-percent fees, recognised by its size since it carries no version
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PoolAccountV0 {
+    pub account_type: u8,
+    pub input_token_mint: Pubkey,
+    pub output_token_mint: Pubkey,
+    pub bp_token_mint: Pubkey,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub scaling_factors: [u128; 2],
+    pub start_weights: [u8; 2],
+    pub end_weights: [u8; 2],
+    pub invariant: u64,
+    pub swap_enabled: bool,
+    pub is_initialized: bool,
+    pub is_vesting: bool,
+    pub is_buy_only: bool,
+    pub swap_fee_percentage: u8,
+    pub flat_rate_percentage: u8,
+    pub fee_collector: Pubkey,
+    pub owner: Pubkey,
+}
+
+impl PoolAccountV0 {
+    pub const LEN: usize = 235;
+}
+
+// Whole-percent weights become 1e18 fixed-point weights
+fn percentage_to_weight(percentage: u8) -> u64 {
+    percentage as u64 * (WEIGHT_ONE / 100)
+}
+
+// Two-token arrays become the first entries of the pool token arrays
+fn widen_to_pool_tokens<T: Copy + Default>(values: [T; 2]) -> [T; MAX_POOL_TOKENS] {
+    let mut widened = [T::default(); MAX_POOL_TOKENS];
+    widened[..2].copy_from_slice(&values);
+    widened
+}
+
+fn percentage_to_bps(percentage: u8) -> u16 {
+    percentage as u16 * 100
+}
+
+impl From<MasterAccountV0> for MasterAccount {
+    fn from(legacy: MasterAccountV0) -> Self {
+        MasterAccount {
+            account_type: legacy.account_type,
+            version: MASTER_ACCOUNT_VERSION,
+            admin: legacy.admin,
+            protocol_swap_fee_bps: percentage_to_bps(legacy.protocol_swap_fee_percentage),
+            protocol_flat_rate_bps: percentage_to_bps(legacy.protocol_flat_rate_percentage),
+            fee_collector: legacy.fee_collector,
+            fee_change_delay: MIN_FEE_CHANGE_DELAY,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<PoolAccountV0> for PoolAccount {
+    fn from(legacy: PoolAccountV0) -> Self {
+        // The baseline predates the status, it follows from the flags. Swaps were not tracked either,
+        // so any funded pool is assumed to have been traded
+        let status = if !legacy.is_initialized {
+            PoolStatus::Created
+        } else if legacy.swap_enabled {
+            PoolStatus::Funded
+        } else {
+            PoolStatus::Paused
+        };
+        PoolAccount {
+            account_type: legacy.account_type,
+            version: POOL_ACCOUNT_VERSION,
+            input_token_mint: legacy.input_token_mint,
+            output_token_mint: legacy.output_token_mint,
+            bp_token_mint: legacy.bp_token_mint,
+            start_timestamp: legacy.start_timestamp,
+            end_timestamp: legacy.end_timestamp,
+            scaling_factors: widen_to_pool_tokens(legacy.scaling_factors),
+            start_weights: widen_to_pool_tokens(legacy.start_weights.map(percentage_to_weight)),
+            end_weights: widen_to_pool_tokens(legacy.end_weights.map(percentage_to_weight)),
+            invariant: legacy.invariant,
+            swap_enabled: legacy.swap_enabled,
+            is_initialized: legacy.is_initialized,
+            is_vesting: legacy.is_vesting,
+            is_buy_only: legacy.is_buy_only,
+            swap_fee_bps: percentage_to_bps(legacy.swap_fee_percentage),
+            flat_rate_bps: percentage_to_bps(legacy.flat_rate_percentage),
+            fee_collector: legacy.fee_collector,
+            owner: legacy.owner,
+            token_count: 2,
+            status,
+            has_swapped: legacy.is_initialized,
+            ..Default::default()
+        }
+    }
+}
+
+// Deserializes an account written with an older layout, after checking it belongs to this program
+pub fn read_legacy_account<T: AnchorDeserialize>(
+    account: &AccountInfo,
+    discriminator: [u8; 8],
+) -> Result<T> {
+    if account.owner != &crate::ID {
+        return Err(ErrorCode::AccountOwnedByWrongProgram.into());
+    }
+    let data = account.try_borrow_data()?;
+    if data.len() < 8 || data[..8] != discriminator {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+    let legacy = T::deserialize(&mut &data[8..])?;
+    Ok(legacy)
+}
+
+// Reads the layout version stored after the discriminator and `account_type`
+pub fn read_account_version(account: &AccountInfo, discriminator: [u8; 8]) -> Result<u8> {
+    if account.owner != &crate::ID {
+        return Err(ErrorCode::AccountOwnedByWrongProgram.into());
+    }
+    let data = account.try_borrow_data()?;
+    if data.len() < 10 || data[..8] != discriminator {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+    Ok(data[9])
+}
+
+// Resizes the account to `len`, topping up rent from `payer`, and writes the upgraded state
+pub fn write_migrated_account<'info, T: AccountSerialize>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    migrated: &T,
+    len: usize,
+) -> Result<()> {
+    let rent = Rent::get()?;
+    let missing_lamports = rent.minimum_balance(len).saturating_sub(account.lamports());
+    if missing_lamports > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            missing_lamports,
+        )?;
+    }
+    account.realloc(len, false)?;
+    let mut data = account.try_borrow_mut_data()?;
+    migrated.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+// Upgrades the master account from the layout version it stores
+pub fn migrate_master_account<'info>(
+    master_account: &AccountInfo<'info>,
+    admin: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    match read_account_version(master_account, MasterAccount::DISCRIMINATOR)? {
+        0 => {}
+        MASTER_ACCOUNT_VERSION => return Err(NeptuneError::AlreadyMigrated.into()),
+        _ => return Err(NeptuneError::UnsupportedAccountVersion.into()),
+    }
+    let legacy: MasterAccountV0 =
+        read_legacy_account(master_account, MasterAccount::DISCRIMINATOR)?;
+    if legacy.admin != *admin.key {
+        return Err(ErrorCode::ConstraintHasOne.into());
+    }
+    write_migrated_account(
+        master_account,
+        admin,
+        system_program,
+        &MasterAccount::from(legacy),
+        MasterAccount::LEN,
+    )
+}
+
+// Upgrades the pool account from the layout version it stores. Baseline pools carry no version field,
+// they are the only layout recognised by its size
+pub fn migrate_pool_account<'info>(
+    pool_account: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    pool_index: u64,
+) -> Result<()> {
+    let version = if pool_account.data_len() == PoolAccountV0::LEN {
+        0
+    } else {
+        read_account_version(pool_account, PoolAccount::DISCRIMINATOR)?
+    };
+    match version {
+        0 => {}
+        POOL_ACCOUNT_VERSION => return Err(NeptuneError::AlreadyMigrated.into()),
+        _ => return Err(NeptuneError::UnsupportedAccountVersion.into()),
+    }
+    let legacy: PoolAccountV0 = read_legacy_account(pool_account, PoolAccount::DISCRIMINATOR)?;
+    if legacy.owner != *owner.key {
+        return Err(NeptuneError::UnAuthorizedPoolOwner.into());
+    }
+    // the pool account seeds are derived from the index, keep it so later instructions find the account
+    let migrated = PoolAccount {
+        pool_index,
+        ..PoolAccount::from(legacy)
+    };
+    write_migrated_account(
+        pool_account,
+        owner,
+        system_program,
+        &migrated,
+        PoolAccount::LEN,
+    )
+}
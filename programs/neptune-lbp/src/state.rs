@@ -1,34 +1,779 @@
 use anchor_lang::prelude::*;
+use crate::math::{WeightedMath, WeightedMathTrait, BPS_DENOMINATOR, WEIGHT_ONE};
+use crate::NeptuneError;
+use num::bigint::BigInt;
 use solana_maths::{Decimal, MathError, U128}; //Precision lib for scaled amount
 
 #[account]
+#[derive(Default, InitSpace)]
 pub struct MasterAccount {
     pub account_type: u8,
     pub version: u8,
     pub admin: Pubkey,
-    pub protocol_swap_fee_percentage: u8,
-    pub protocol_flat_rate_percentage: u8,
+    pub protocol_swap_fee_bps: u16,
+    pub protocol_flat_rate_bps: u16,
     pub fee_collector: Pubkey,
+    pub pending_admin: Pubkey,
+    pub council: Pubkey,
+    pub fee_change_delay: i64,
+    pub pending_swap_fee_bps: u16,
+    pub pending_flat_rate_bps: u16,
+    pub pending_fee_effective_timestamp: i64,
+    pub guardian: Pubkey,
+    pub paused: bool,
+    pub fee_recipients: [Pubkey; MAX_FEE_RECIPIENTS],
+    pub fee_recipient_shares_bps: [u16; MAX_FEE_RECIPIENTS],
+    pub fee_recipient_count: u8,
+    pub creator_allowlist_enabled: bool,
+    pub referral_fee_share_bps: u16,
+    pub pool_creation_fee: u64,
+    pub pool_creation_fee_mint: Pubkey, // default public key means the fee is paid in SOL
+    pub pending_fee_change_delay: i64,
+    pub pending_delay_effective_timestamp: i64, // 0 when no shorter delay is queued
+    // room for new fields without reallocating, shrink it when adding one
+    pub reserved: [u64; 9],
 }
 
 #[account]
+#[derive(Default, InitSpace)]
 pub struct PoolAccount {
     pub account_type: u8,
+    pub version: u8,
     pub input_token_mint: Pubkey,
     pub output_token_mint: Pubkey,
     pub bp_token_mint: Pubkey,
     pub start_timestamp: i64,
     pub end_timestamp: i64,
-    pub scaling_factors: [u128; 2],
-    pub start_weights: [u8; 2],
-    pub end_weights: [u8; 2],
+    pub scaling_factors: [u128; MAX_POOL_TOKENS],
+    pub start_weights: [u64; MAX_POOL_TOKENS], // 1e18 fixed-point, WEIGHT_ONE being 100%
+    pub end_weights: [u64; MAX_POOL_TOKENS],
     pub invariant: u64,
     pub swap_enabled: bool,
     pub is_initialized: bool,
     pub is_vesting: bool,
     pub is_buy_only: bool,
-    pub swap_fee_percentage: u8,
-    pub flat_rate_percentage: u8,
+    pub swap_fee_bps: u16,
+    pub flat_rate_bps: u16,
     pub fee_collector: Pubkey,
     pub owner: Pubkey,
+    pub token_count: u8,
+    pub additional_token_mints: [Pubkey; MAX_ADDITIONAL_POOL_TOKENS], // tokens 2 and up, the input and output mints being tokens 0 and 1
+    pub has_weight_schedule: bool, // weights follow the pool's `WeightSchedule` instead of the start to end curve
+    pub weight_curve: WeightCurve, // shape of the move from start to end weights
+    pub is_finalized: bool,        // set by `owner_exit`, which can only run once
+    pub status: PoolStatus, // stored lifecycle state, `is_initialized` and `swap_enabled` are kept in line with it
+    pub has_swapped: bool,  // set by the first swap, a traded pool can no longer be cancelled
+    pub pool_index: u64, // position among its creator's pools from 1, 0 for pools derived before the index
+    pub pending_swap_fee_bps: u16,
+    pub pending_flat_rate_bps: u16,
+    pub pending_fee_effective_timestamp: i64, // 0 when no fee increase is queued
+    pub launch_partner: Pubkey, // partner who referred the launch, default public key when there is none
+    pub launch_partner_share_bps: u16, // part of the pool's protocol fees owed to the launch partner
+    pub weight_ramp_start: i64, // window of a ramp started by `update_weights_gradually`, 0 to follow the sale window
+    pub weight_ramp_end: i64,
+    // room for new fields without reallocating, shrink it when adding one
+    pub reserved: [u64; 13],
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CreatorAccount {
+    pub account_type: u8,
+    pub creator: Pubkey,
+    pub pool_count: u64, // pools created so far, the next one gets index pool_count + 1
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeTier {
+    pub account_type: u8,
+    pub creator: Pubkey,
+    pub swap_fee_bps: u16,
+    pub flat_rate_bps: u16,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ApprovedCreator {
+    pub account_type: u8,
+    pub creator: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ReferralAccount {
+    pub account_type: u8,
+    pub referrer: Pubkey,
+    pub token_mint: Pubkey,
+    pub cumulative_volume: u64,
+    pub cumulative_earnings: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PoolFeeLedger {
+    pub account_type: u8,
+    pub pool: Pubkey,
+    pub token_mints: [Pubkey; MAX_POOL_TOKENS], // pool token mints, the fee arrays follow this order
+    pub swap_fees: [u64; MAX_POOL_TOKENS],
+    pub referral_fees: [u64; MAX_POOL_TOKENS], // part of the swap fees paid out to referrers
+    pub flat_rate_fees: [u64; MAX_POOL_TOKENS],
+    pub partner_fees_paid: [u64; MAX_POOL_TOKENS], // part of the protocol fees paid out to the launch partner
+}
+
+// Launch partner fees of one mint held by the master account or the SOL fee vault and not paid yet,
+// left in place by the fee sweeps until `pay_launch_partner` pays them
+#[account]
+#[derive(InitSpace)]
+pub struct PartnerFeeReserve {
+    pub account_type: u8,
+    pub token_mint: Pubkey, // default public key for SOL fees
+    pub fees_due: u64,
+}
+
+// Lifecycle of a pool. Created, Funded, Paused, Finalized and Cancelled are stored in `PoolAccount::status`;
+// a funded pool goes through Scheduled, Live and Ended with the clock, as `PoolAccount::status_at` derives
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub enum PoolStatus {
+    #[default]
+    Created, // initialized, not funded yet
+    Funded,    // holds its liquidity, the sale runs between the start and end timestamps
+    Scheduled, // funded, before the start of the sale
+    Live,      // between the start and end of the sale, swaps enabled
+    Paused,    // swaps stopped by the owner until resumed or the end of the sale
+    Ended,     // after the end of the sale, BP tokens can be redeemed
+    Finalized, // the owner exited the pool
+    Closed,    // set by `close_pool` as the pool account is closed
+    Cancelled, // deposits refunded by `cancel_pool` before any trade, can only be closed
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub enum WeightCurve {
+    #[default]
+    Linear,
+    Exponential, // slow start, the weights move faster towards the end
+    Logarithmic, // fast start, the weights flatten out towards the end
+    Sigmoid,     // slow start and end, fastest around the middle of the sale
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct WeightWaypoint {
+    pub timestamp: i64,
+    pub weights: [u64; MAX_POOL_TOKENS], // 1e18 fixed-point, WEIGHT_ONE being 100%
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct WeightSchedule {
+    pub account_type: u8,
+    pub pool: Pubkey,
+    pub waypoint_count: u8,
+    pub waypoints: [WeightWaypoint; MAX_WEIGHT_WAYPOINTS], // sorted by timestamp, weights move linearly between neighbours
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PoolMetadata {
+    pub account_type: u8,
+    pub pool: Pubkey,
+    #[max_len(MAX_POOL_NAME_LEN)]
+    pub name: String,
+    #[max_len(MAX_POOL_SYMBOL_LEN)]
+    pub symbol: String,
+    #[max_len(MAX_POOL_URI_LEN)]
+    pub uri: String, // off-chain description of the sale, also the URI of the BP token metadata
+    #[max_len(MAX_POOL_SOCIAL_LINKS, MAX_POOL_SOCIAL_LINK_LEN)]
+    pub social_links: Vec<String>,
+}
+
+pub const MAX_COUNCIL_MEMBERS: usize = 10;
+pub const MAX_FEE_RECIPIENTS: usize = 5;
+pub const MAX_POOL_TOKENS: usize = 8;
+// Pool tokens past the input and output tokens
+pub const MAX_ADDITIONAL_POOL_TOKENS: usize = MAX_POOL_TOKENS - 2;
+pub const MAX_WEIGHT_WAYPOINTS: usize = 16;
+// Pool metadata field lengths in bytes. The symbol leaves room for the "-LBP" suffix of the BP token
+// symbol within the 10 bytes token metadata allows
+pub const MAX_POOL_NAME_LEN: usize = 32;
+pub const MAX_POOL_SYMBOL_LEN: usize = 6;
+pub const MAX_POOL_URI_LEN: usize = 200;
+pub const MAX_POOL_SOCIAL_LINKS: usize = 4;
+pub const MAX_POOL_SOCIAL_LINK_LEN: usize = 100;
+// How far a token weight may move per hour of a ramp started with `update_weights_gradually`, 10 percentage points
+pub const MAX_WEIGHT_CHANGE_PER_HOUR: u64 = WEIGHT_ONE / 10;
+// Hard caps on protocol fees in basis points, so a queued change can never exceed them
+pub const MAX_PROTOCOL_SWAP_FEE_BPS: u16 = 1_000;
+pub const MAX_PROTOCOL_FLAT_RATE_BPS: u16 = 1_000;
+// Cap on the referrer's share of the swap fee, so most of the fee always reaches the protocol
+pub const MAX_REFERRAL_FEE_SHARE_BPS: u16 = 2_500;
+// Layout versions, bumped whenever an account needs `migrate_master_account` / `migrate_pool_account`
+pub const MASTER_ACCOUNT_VERSION: u8 = 1;
+pub const POOL_ACCOUNT_VERSION: u8 = 1;
+// Bounds on the notice period given before a fee change takes effect, in seconds
+pub const MIN_FEE_CHANGE_DELAY: i64 = 24 * 60 * 60;
+pub const MAX_FEE_CHANGE_DELAY: i64 = 30 * 24 * 60 * 60;
+// Time a council proposal stays open for approvals and execution, in seconds
+pub const COUNCIL_PROPOSAL_LIFETIME: i64 = 7 * 24 * 60 * 60;
+// Time after the end of a sale during which BP holders can redeem before the owner may close the pool anyway,
+// leaving their share of the balances in the pool for them to redeem later
+pub const POOL_CLOSE_GRACE_PERIOD: i64 = 90 * 24 * 60 * 60;
+
+#[account]
+#[derive(InitSpace)]
+pub struct AdminCouncil {
+    pub account_type: u8,
+    pub members: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub member_count: u8,
+    pub threshold: u8,
+    pub proposal_count: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub enum CouncilAction {
+    SetFeePercentage { swap_fee_bps: u16, flat_rate_bps: u16 },
+    SetFeeCollector { fee_collector: Pubkey },
+    ProposeAdmin { admin: Pubkey },
+    ReplaceMember { old_member: Pubkey, new_member: Pubkey },
+    SetThreshold { threshold: u8 },
+    CancelFeeChange,
+    SetFeeChangeDelay { delay: i64 },
+    SetPoolCreationFee { fee: u64, fee_mint: Pubkey },
+    SetReferralFeeShare { share_bps: u16 },
+    CancelAdminProposal,
+    SetGuardian { guardian: Pubkey },
+    SetPoolFees { pool: Pubkey, swap_fee_bps: u16, flat_rate_bps: u16 },
+    SetFeeTier { creator: Pubkey, swap_fee_bps: u16, flat_rate_bps: u16 },
+    RemoveFeeTier { creator: Pubkey },
+    SetFeeDistribution {
+        recipients: [Pubkey; MAX_FEE_RECIPIENTS],
+        shares_bps: [u16; MAX_FEE_RECIPIENTS],
+        recipient_count: u8,
+    },
+    SetLaunchPartner { pool: Pubkey, partner: Pubkey, share_bps: u16 },
+    SetCreatorAllowlist { enabled: bool },
+    ApproveCreator { creator: Pubkey },
+    RevokeCreator { creator: Pubkey },
+}
+
+impl CouncilAction {
+    // Creator the `fee_tier` and `approved_creator` accounts of `execute_council_proposal` are derived from
+    pub fn creator_seed(&self) -> &[u8] {
+        match self {
+            CouncilAction::SetFeeTier { creator, .. }
+            | CouncilAction::RemoveFeeTier { creator }
+            | CouncilAction::ApproveCreator { creator }
+            | CouncilAction::RevokeCreator { creator } => creator.as_ref(),
+            _ => &[],
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CouncilProposal {
+    pub account_type: u8,
+    pub council: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub action: CouncilAction,
+    pub approvers: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub approval_count: u8,
+    pub executed: bool,
+    pub expires_at: i64, // approvals and execution are rejected after this timestamp
+}
+
+impl MasterAccount {
+    pub fn is_pause_authority(&self, key: &Pubkey) -> bool {
+        *key == self.admin || (self.guardian != Pubkey::default() && *key == self.guardian)
+    }
+
+    pub const LEN: usize = 8 + MasterAccount::INIT_SPACE;
+
+    pub fn set_fee_distribution(&mut self, recipients: &[Pubkey], shares_bps: &[u16]) -> Result<()> {
+        if recipients.len() != shares_bps.len() || recipients.len() > MAX_FEE_RECIPIENTS {
+            return Err(NeptuneError::InvalidFeeDistribution.into());
+        }
+        let total_shares: u32 = shares_bps.iter().map(|share| *share as u32).sum();
+        if !recipients.is_empty() && total_shares != BPS_DENOMINATOR as u32 {
+            return Err(NeptuneError::InvalidFeeDistribution.into());
+        }
+        for (i, recipient) in recipients.iter().enumerate() {
+            if *recipient == Pubkey::default() || recipients[..i].contains(recipient) {
+                return Err(NeptuneError::InvalidFeeDistribution.into());
+            }
+        }
+        self.fee_recipients = [Pubkey::default(); MAX_FEE_RECIPIENTS];
+        self.fee_recipient_shares_bps = [0u16; MAX_FEE_RECIPIENTS];
+        self.fee_recipients[..recipients.len()].copy_from_slice(recipients);
+        self.fee_recipient_shares_bps[..shares_bps.len()].copy_from_slice(shares_bps);
+        self.fee_recipient_count = recipients.len() as u8;
+        Ok(())
+    }
+
+    // Splits `amount` following the fee distribution table, the last recipient taking the rounding dust
+    pub fn split_fees(&self, amount: u64) -> Vec<u64> {
+        let count = self.fee_recipient_count as usize;
+        let mut shares = Vec::with_capacity(count);
+        let mut distributed = 0u64;
+        for i in 0..count {
+            let share = if i + 1 == count {
+                amount - distributed
+            } else {
+                (amount as u128 * self.fee_recipient_shares_bps[i] as u128
+                    / BPS_DENOMINATOR as u128) as u64
+            };
+            distributed += share;
+            shares.push(share);
+        }
+        shares
+    }
+
+    pub fn has_pending_fee_change(&self) -> bool {
+        self.pending_fee_effective_timestamp != 0
+    }
+
+    // Queues a fee change that can only be applied once `fee_change_delay` has elapsed
+    pub fn queue_fee_change(&mut self, swap_fee_bps: u16, flat_rate_bps: u16, now: i64) -> Result<()> {
+        validate_fee_caps(swap_fee_bps, flat_rate_bps)?;
+        self.apply_pending_fee_change_delay(now);
+        self.pending_swap_fee_bps = swap_fee_bps;
+        self.pending_flat_rate_bps = flat_rate_bps;
+        self.pending_fee_effective_timestamp = now + self.fee_change_delay;
+        Ok(())
+    }
+
+    // A longer delay applies at once. A shorter one only applies after the current delay, so a fee change
+    // queued right after the reduction still comes with the notice promised when it was requested
+    pub fn set_fee_change_delay(&mut self, delay: i64, now: i64) -> Result<()> {
+        if delay < MIN_FEE_CHANGE_DELAY || delay > MAX_FEE_CHANGE_DELAY {
+            return Err(NeptuneError::InitParamError.into());
+        }
+        self.apply_pending_fee_change_delay(now);
+        if delay >= self.fee_change_delay {
+            self.fee_change_delay = delay;
+            self.pending_delay_effective_timestamp = 0;
+        } else {
+            self.pending_fee_change_delay = delay;
+            self.pending_delay_effective_timestamp = now + self.fee_change_delay;
+        }
+        Ok(())
+    }
+
+    // Delay in force at `now`, counting a queued reduction once it is due
+    pub fn fee_change_delay_at(&self, now: i64) -> i64 {
+        if self.pending_delay_effective_timestamp != 0
+            && now >= self.pending_delay_effective_timestamp
+        {
+            self.pending_fee_change_delay
+        } else {
+            self.fee_change_delay
+        }
+    }
+
+    pub fn apply_pending_fee_change_delay(&mut self, now: i64) {
+        if self.pending_delay_effective_timestamp != 0
+            && now >= self.pending_delay_effective_timestamp
+        {
+            self.fee_change_delay = self.pending_fee_change_delay;
+            self.pending_delay_effective_timestamp = 0;
+        }
+    }
+}
+
+pub fn validate_referral_fee_share(share_bps: u16) -> Result<()> {
+    if share_bps > MAX_REFERRAL_FEE_SHARE_BPS {
+        return Err(NeptuneError::FeeAboveMaximum.into());
+    }
+    Ok(())
+}
+
+// Shared by protocol fee changes, pool overrides and fee tiers
+pub fn validate_fee_caps(swap_fee_bps: u16, flat_rate_bps: u16) -> Result<()> {
+    if swap_fee_bps > MAX_PROTOCOL_SWAP_FEE_BPS || flat_rate_bps > MAX_PROTOCOL_FLAT_RATE_BPS {
+        return Err(NeptuneError::FeeAboveMaximum.into());
+    }
+    Ok(())
+}
+
+impl ReferralAccount {
+    pub fn record_swap(&mut self, volume: u64, earnings: u64) -> Result<()> {
+        self.cumulative_volume = self
+            .cumulative_volume
+            .checked_add(volume)
+            .ok_or(NeptuneError::MathError)?;
+        self.cumulative_earnings = self
+            .cumulative_earnings
+            .checked_add(earnings)
+            .ok_or(NeptuneError::MathError)?;
+        Ok(())
+    }
+}
+
+impl PoolFeeLedger {
+    // Ledgers of pools created before the ledger existed are opened on their first fee
+    pub fn initialize_if_needed(&mut self, pool: Pubkey, token_mints: &[Pubkey]) {
+        if self.account_type == 0 {
+            self.account_type = 8;
+            self.pool = pool;
+            self.token_mints[..token_mints.len()].copy_from_slice(token_mints);
+        }
+    }
+
+    pub fn record_swap_fee(
+        &mut self,
+        mint_index: usize,
+        amount: u64,
+        referral_amount: u64,
+    ) -> Result<()> {
+        self.swap_fees[mint_index] = self.swap_fees[mint_index]
+            .checked_add(amount)
+            .ok_or(NeptuneError::MathError)?;
+        self.referral_fees[mint_index] = self.referral_fees[mint_index]
+            .checked_add(referral_amount)
+            .ok_or(NeptuneError::MathError)?;
+        Ok(())
+    }
+
+    // Launch partner share of the protocol fees recorded so far, less what it was already paid
+    pub fn partner_fees_due(&self, mint_index: usize, share_bps: u16) -> u64 {
+        let protocol_fees = self.swap_fees[mint_index] as u128
+            - self.referral_fees[mint_index] as u128
+            + self.flat_rate_fees[mint_index] as u128;
+        let share = (protocol_fees * share_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        share.saturating_sub(self.partner_fees_paid[mint_index])
+    }
+
+    pub fn record_flat_rate_fee(&mut self, mint_index: usize, amount: u64) -> Result<()> {
+        self.flat_rate_fees[mint_index] = self.flat_rate_fees[mint_index]
+            .checked_add(amount)
+            .ok_or(NeptuneError::MathError)?;
+        Ok(())
+    }
+}
+
+impl PartnerFeeReserve {
+    // Reserves are opened on the first partner fee of their mint, or on the first payment of fees taken before them
+    pub fn initialize_if_needed(&mut self, token_mint: Pubkey) {
+        if self.account_type == 0 {
+            self.account_type = 12;
+            self.token_mint = token_mint;
+        }
+    }
+
+    pub fn accrue(&mut self, amount: u64) -> Result<()> {
+        self.fees_due = self
+            .fees_due
+            .checked_add(amount)
+            .ok_or(NeptuneError::MathError)?;
+        Ok(())
+    }
+
+    // Fees accrued before the reserve existed were never reserved, so payments can exceed it
+    pub fn release(&mut self, amount: u64) {
+        self.fees_due = self.fees_due.saturating_sub(amount);
+    }
+}
+
+// Last `pool_account` seed before the bump: the pool index, or nothing for pools created before it,
+// an empty seed leaving their address unchanged
+pub fn pool_index_seed(pool_index: u64) -> Vec<u8> {
+    if pool_index == 0 {
+        Vec::new()
+    } else {
+        pool_index.to_le_bytes().to_vec()
+    }
+}
+
+impl PoolAccount {
+    pub const LEN: usize = 8 + PoolAccount::INIT_SPACE;
+
+    pub fn token_mint(&self, index: usize) -> Pubkey {
+        match index {
+            0 => self.input_token_mint,
+            1 => self.output_token_mint,
+            _ => self.additional_token_mints[index - 2],
+        }
+    }
+
+    pub fn pool_index_seed(&self) -> Vec<u8> {
+        pool_index_seed(self.pool_index)
+    }
+
+    pub fn has_pending_fee_change(&self) -> bool {
+        self.pending_fee_effective_timestamp != 0
+    }
+
+    // Fee overrides apply at once before funding and whenever they lower the fees. Raising the fees of a
+    // funded pool is queued for `delay`, giving its traders the notice protocol fee changes come with
+    pub fn queue_fee_change(
+        &mut self,
+        swap_fee_bps: u16,
+        flat_rate_bps: u16,
+        delay: i64,
+        now: i64,
+    ) -> Result<()> {
+        validate_fee_caps(swap_fee_bps, flat_rate_bps)?;
+        if self.status == PoolStatus::Created
+            || (swap_fee_bps <= self.swap_fee_bps && flat_rate_bps <= self.flat_rate_bps)
+        {
+            self.swap_fee_bps = swap_fee_bps;
+            self.flat_rate_bps = flat_rate_bps;
+            self.pending_fee_effective_timestamp = 0;
+        } else {
+            self.pending_swap_fee_bps = swap_fee_bps;
+            self.pending_flat_rate_bps = flat_rate_bps;
+            self.pending_fee_effective_timestamp = now + delay;
+        }
+        Ok(())
+    }
+
+    // The partner is fixed before funding, so everything the ledger records is owed to the same partner
+    pub fn set_launch_partner(&mut self, partner: Pubkey, share_bps: u16) -> Result<()> {
+        if self.status != PoolStatus::Created {
+            return Err(NeptuneError::InvalidPoolStatus.into());
+        }
+        if share_bps as u64 > BPS_DENOMINATOR || (partner == Pubkey::default() && share_bps != 0) {
+            return Err(NeptuneError::InvalidFeeDistribution.into());
+        }
+        self.launch_partner = partner;
+        self.launch_partner_share_bps = share_bps;
+        Ok(())
+    }
+
+    pub fn token_mints(&self) -> Vec<Pubkey> {
+        (0..self.token_count as usize)
+            .map(|index| self.token_mint(index))
+            .collect()
+    }
+
+    pub fn token_index(&self, mint: &Pubkey) -> Option<usize> {
+        (0..self.token_count as usize).find(|&index| self.token_mint(index) == *mint)
+    }
+
+    // Recomputes the stored invariant from scaled `balances` at `weights`, downscaled like the one set by
+    // `initialize_pool_funds`
+    pub fn update_invariant(&mut self, weights: &[BigInt], balances: &[BigInt]) -> Result<()> {
+        let invariant = WeightedMath::calculate_invariant(weights, balances)
+            .map_err(|_| NeptuneError::MathError)?;
+        self.invariant = WeightedMath::downscale_value(&invariant, 1_000_000_000)?;
+        Ok(())
+    }
+
+    // Weights of every pool token at `current_timestamp`, normalized to 1e18
+    // Pools with a weight schedule must pass it, the others move from start to end weights along their curve
+    pub fn current_weights(
+        &self,
+        current_timestamp: i64,
+        weight_schedule: Option<&WeightSchedule>,
+    ) -> Result<Vec<BigInt>> {
+        Ok(self
+            .current_fixed_weights(current_timestamp, weight_schedule)?
+            .into_iter()
+            .map(BigInt::from)
+            .collect())
+    }
+
+    // Status of the pool at `current_timestamp`, with the phase of the sale of funded and paused pools
+    pub fn status_at(&self, current_timestamp: i64) -> PoolStatus {
+        match self.status {
+            PoolStatus::Funded | PoolStatus::Paused if current_timestamp > self.end_timestamp => {
+                PoolStatus::Ended
+            }
+            PoolStatus::Funded if current_timestamp < self.start_timestamp => PoolStatus::Scheduled,
+            PoolStatus::Funded => PoolStatus::Live,
+            status => status,
+        }
+    }
+
+    pub fn require_status(&self, current_timestamp: i64, allowed: &[PoolStatus]) -> Result<()> {
+        if !allowed.contains(&self.status_at(current_timestamp)) {
+            return Err(NeptuneError::InvalidPoolStatus.into());
+        }
+        Ok(())
+    }
+
+    // Swaps only run while the sale is live
+    pub fn require_live(&self, current_timestamp: i64) -> Result<()> {
+        match self.status_at(current_timestamp) {
+            PoolStatus::Live => Ok(()),
+            PoolStatus::Paused => Err(NeptuneError::OnPause.into()),
+            PoolStatus::Ended | PoolStatus::Finalized => {
+                Err(NeptuneError::ScheduleFinalised.into())
+            }
+            _ => Err(NeptuneError::InvalidPoolStatus.into()),
+        }
+    }
+
+    // Moves the pool to `next`, rejecting any move the lifecycle does not allow from its current status
+    pub fn transition(&mut self, current_timestamp: i64, next: PoolStatus) -> Result<()> {
+        let allowed = matches!(
+            (self.status_at(current_timestamp), next),
+            (PoolStatus::Created, PoolStatus::Funded)
+                | (PoolStatus::Scheduled | PoolStatus::Live, PoolStatus::Paused)
+                | (PoolStatus::Paused, PoolStatus::Funded)
+                | (PoolStatus::Ended, PoolStatus::Finalized)
+                | (
+                    PoolStatus::Scheduled | PoolStatus::Live | PoolStatus::Paused,
+                    PoolStatus::Cancelled
+                )
+                | (
+                    PoolStatus::Ended | PoolStatus::Finalized | PoolStatus::Cancelled,
+                    PoolStatus::Closed
+                )
+        );
+        if !allowed {
+            return Err(NeptuneError::InvalidPoolStatus.into());
+        }
+        self.status = next;
+        self.is_initialized = next != PoolStatus::Created;
+        self.swap_enabled = next == PoolStatus::Funded;
+        if next == PoolStatus::Finalized {
+            self.is_finalized = true;
+        }
+        Ok(())
+    }
+
+    // Weights move from start to end weights over the sale window, or over the last weight ramp once one was started
+    pub fn weight_ramp_window(&self) -> (i64, i64) {
+        if self.weight_ramp_end == 0 {
+            (self.start_timestamp, self.end_timestamp)
+        } else {
+            (self.weight_ramp_start, self.weight_ramp_end)
+        }
+    }
+
+    // Same as `current_weights`, as the 1e18 fixed-point values the pool stores
+    pub fn current_fixed_weights(
+        &self,
+        current_timestamp: i64,
+        weight_schedule: Option<&WeightSchedule>,
+    ) -> Result<Vec<u64>> {
+        match (self.has_weight_schedule, weight_schedule) {
+            (true, Some(weight_schedule)) => {
+                weight_schedule.weights_at(current_timestamp, self.token_count as usize)
+            }
+            (true, None) => Err(NeptuneError::WeightScheduleRequired.into()),
+            (false, _) => {
+                let (ramp_start, ramp_end) = self.weight_ramp_window();
+                let curve_progress = WeightedMath::calculate_curve_progress(
+                    self.weight_curve,
+                    current_timestamp,
+                    ramp_start,
+                    ramp_end,
+                )?;
+                (0..self.token_count as usize)
+                    .map(|index| {
+                        Ok(WeightedMath::interpolate_weight(
+                            self.start_weights[index],
+                            self.end_weights[index],
+                            curve_progress,
+                        )?)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl WeightSchedule {
+    pub const LEN: usize = 8 + WeightSchedule::INIT_SPACE;
+
+    pub fn waypoints(&self) -> &[WeightWaypoint] {
+        &self.waypoints[..self.waypoint_count as usize]
+    }
+
+    // Waypoints must cover the pool's sale window and carry valid weights for each of its tokens
+    pub fn set_waypoints(&mut self, pool: &PoolAccount, waypoints: &[WeightWaypoint]) -> Result<()> {
+        if waypoints.len() < 2 || waypoints.len() > MAX_WEIGHT_WAYPOINTS {
+            return Err(NeptuneError::InvalidWeightSchedule.into());
+        }
+        if waypoints[0].timestamp != pool.start_timestamp
+            || waypoints[waypoints.len() - 1].timestamp != pool.end_timestamp
+            || waypoints
+                .windows(2)
+                .any(|pair| pair[0].timestamp >= pair[1].timestamp)
+        {
+            return Err(NeptuneError::InvalidWeightSchedule.into());
+        }
+        let token_count = pool.token_count as usize;
+        for waypoint in waypoints {
+            validate_pool_weights(&waypoint.weights[..token_count])?;
+            if waypoint.weights[token_count..].iter().any(|weight| *weight != 0) {
+                return Err(NeptuneError::InvalidWeightSchedule.into());
+            }
+        }
+
+        self.waypoints = [WeightWaypoint::default(); MAX_WEIGHT_WAYPOINTS];
+        self.waypoints[..waypoints.len()].copy_from_slice(waypoints);
+        self.waypoint_count = waypoints.len() as u8;
+        Ok(())
+    }
+
+    // Weights of the first `token_count` tokens, interpolated within the segment holding `current_timestamp`
+    pub fn weights_at(&self, current_timestamp: i64, token_count: usize) -> Result<Vec<u64>> {
+        let waypoints = self.waypoints();
+        let first = waypoints[0];
+        let last = waypoints[waypoints.len() - 1];
+        if current_timestamp <= first.timestamp {
+            return Ok(first.weights[..token_count].to_vec());
+        }
+        if current_timestamp >= last.timestamp {
+            return Ok(last.weights[..token_count].to_vec());
+        }
+
+        let segment = waypoints
+            .windows(2)
+            .find(|pair| current_timestamp < pair[1].timestamp)
+            .ok_or(NeptuneError::InvalidWeightSchedule)?;
+        let progress = WeightedMath::calculate_weight_change_progress(
+            current_timestamp,
+            segment[0].timestamp,
+            segment[1].timestamp,
+        )?;
+        (0..token_count)
+            .map(|index| {
+                Ok(WeightedMath::interpolate_weight(
+                    segment[0].weights[index],
+                    segment[1].weights[index],
+                    progress,
+                )?)
+            })
+            .collect()
+    }
+}
+
+// Weights are 1e18 fixed-point values that must cover the pool exactly, each token keeping a share
+pub fn validate_pool_weights(weights: &[u64]) -> Result<()> {
+    if weights.iter().any(|weight| *weight == 0)
+        || weights.iter().map(|weight| *weight as u128).sum::<u128>() != WEIGHT_ONE as u128
+    {
+        return Err(NeptuneError::InitParamError.into());
+    }
+    Ok(())
+}
+
+impl AdminCouncil {
+    pub fn is_member(&self, key: &Pubkey) -> bool {
+        self.members[..self.member_count as usize].contains(key)
+    }
+}
+
+impl CouncilProposal {
+    // Approvals from keys that were removed from the council since they approved are not counted
+    pub fn current_approvals(&self, council: &AdminCouncil) -> u8 {
+        self.approvers[..self.approval_count as usize]
+            .iter()
+            .filter(|approver| council.is_member(approver))
+            .count() as u8
+    }
+
+    pub fn require_open(&self, now: i64) -> Result<()> {
+        if self.executed {
+            return Err(NeptuneError::ProposalAlreadyExecuted.into());
+        }
+        if now > self.expires_at {
+            return Err(NeptuneError::ProposalExpired.into());
+        }
+        Ok(())
+    }
 }
@@ -1,8 +1,13 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, TransferChecked};
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{self, TokenAccount, TransferChecked};
 use anchor_spl::token_2022::{self, Transfer, ID as T22ID};
 use solana_program::{program::invoke, program::invoke_signed, system_instruction};
 
+use crate::state::{PartnerFeeReserve, PoolAccount};
+use crate::NeptuneError;
+
 fn transfer_token_2022<'info>(
     token_program: AccountInfo<'info>,  // Token-2022 program account
     source_account: AccountInfo<'info>, // Source token account
@@ -168,3 +173,196 @@ pub fn transfer_router<'info>(
     }
     Ok(())
 }
+
+// SOL fees are paid into the SOL fee vault, a system account PDA separate from the master account.
+// The payer also covers the vault's rent exemption while it is unfunded, since a transfer leaving it
+// below the minimum would be rejected.
+pub fn transfer_sol_fees<'info>(
+    source_account: AccountInfo<'info>,
+    sol_fee_vault: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    amount: u64,
+) -> Result<()> {
+    let missing_rent = Rent::get()?
+        .minimum_balance(0)
+        .saturating_sub(sol_fee_vault.lamports());
+    transfer_router(
+        true,
+        None,
+        source_account.clone(),
+        None,
+        sol_fee_vault,
+        Some(source_account),
+        signer_seeds,
+        amount + missing_rent,
+        None,
+        Some(system_program),
+    )
+}
+
+// Lamports of the SOL fee vault that can leave it while keeping it rent exempt
+pub fn available_sol_fees(sol_fee_vault: &AccountInfo) -> Result<u64> {
+    Ok(sol_fee_vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0)))
+}
+
+// The SOL of native SOL pools is held by the pool account, which this program owns: the system program
+// cannot debit it, so lamports are moved directly
+pub fn transfer_pool_sol(
+    pool_account: &AccountInfo,
+    destination_account: &AccountInfo,
+    amount: u64,
+) -> Result<()> {
+    let pool_lamports = pool_account
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(NeptuneError::MathError)?;
+    let destination_lamports = destination_account
+        .lamports()
+        .checked_add(amount)
+        .ok_or(NeptuneError::MathError)?;
+    **pool_account.try_borrow_mut_lamports()? = pool_lamports;
+    **destination_account.try_borrow_mut_lamports()? = destination_lamports;
+    Ok(())
+}
+
+// Same as `transfer_sol_fees` for SOL fees taken out of a pool account
+pub fn transfer_pool_sol_fees(
+    pool_account: &AccountInfo,
+    sol_fee_vault: &AccountInfo,
+    amount: u64,
+) -> Result<()> {
+    let missing_rent = Rent::get()?
+        .minimum_balance(0)
+        .saturating_sub(sol_fee_vault.lamports());
+    transfer_pool_sol(pool_account, sol_fee_vault, amount + missing_rent)
+}
+
+// Pool tokens past the input and output ones have no named accounts, so their token accounts come in
+// the remaining accounts: for each token from `first_token` on, the associated token account of every
+// key of `owners`, in that order
+pub fn load_pool_token_accounts<'info>(
+    accounts: &'info [AccountInfo<'info>],
+    pool_account: &PoolAccount,
+    first_token: usize,
+    owners: &[Pubkey],
+) -> Result<Vec<Account<'info, TokenAccount>>> {
+    let token_count = pool_account.token_count as usize;
+    if first_token > token_count || accounts.len() != (token_count - first_token) * owners.len() {
+        return Err(NeptuneError::InvalidPoolTokenAccount.into());
+    }
+    let mut token_accounts = Vec::with_capacity(accounts.len());
+    for (i, account) in accounts.iter().enumerate() {
+        let mint = pool_account.token_mint(first_token + i / owners.len());
+        let owner = owners[i % owners.len()];
+        if *account.key != get_associated_token_address(&owner, &mint) {
+            return Err(NeptuneError::InvalidPoolTokenAccount.into());
+        }
+        token_accounts.push(Account::<TokenAccount>::try_from(account)?);
+    }
+    Ok(token_accounts)
+}
+
+// Adds the launch partner's part of fees just recorded on a pool's fee ledger to the reserve of their mint,
+// which must be passed once the pool has a launch partner
+pub fn reserve_partner_fees(
+    partner_fee_reserve: Option<&mut PartnerFeeReserve>,
+    token_mint: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    match partner_fee_reserve {
+        Some(partner_fee_reserve) => {
+            partner_fee_reserve.initialize_if_needed(token_mint);
+            partner_fee_reserve.accrue(amount)
+        }
+        None => Err(NeptuneError::InvalidPartnerFeeReserve.into()),
+    }
+}
+
+// Fee instructions taking fees in several tokens get the partner fee reserves in the remaining accounts.
+// A reserve is created on the first partner fee of its mint, `payer` covering its rent
+pub fn load_partner_fee_reserve<'info>(
+    account: &'info AccountInfo<'info>,
+    token_mint: &Pubkey,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<Account<'info, PartnerFeeReserve>> {
+    let (address, bump) =
+        Pubkey::find_program_address(&[b"partner_fee_reserve", token_mint.as_ref()], &crate::ID);
+    if *account.key != address {
+        return Err(NeptuneError::InvalidPartnerFeeReserve.into());
+    }
+    if account.data_is_empty() {
+        let bump_seed = [bump];
+        let seeds = &[
+            b"partner_fee_reserve".as_ref(),
+            token_mint.as_ref(),
+            &bump_seed[..],
+        ];
+        let signer: &[&[&[u8]]] = &[seeds];
+        let space = 8 + PartnerFeeReserve::INIT_SPACE;
+        // the address may already hold lamports, so it is funded, allocated and assigned in separate steps
+        let missing_lamports = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(account.lamports());
+        if missing_lamports > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer {
+                        from: payer.clone(),
+                        to: account.clone(),
+                    },
+                ),
+                missing_lamports,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Allocate {
+                    account_to_allocate: account.clone(),
+                },
+                signer,
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Assign {
+                    account_to_assign: account.clone(),
+                },
+                signer,
+            ),
+            &crate::ID,
+        )?;
+        account.try_borrow_mut_data()?[..8].copy_from_slice(&PartnerFeeReserve::DISCRIMINATOR);
+    }
+    Account::try_from(account)
+}
+
+// Launch partner fees of `token_mint` that fee sweeps must leave in place, none when the mint never had any
+pub fn reserved_partner_fees(
+    partner_fee_reserve: &AccountInfo,
+    token_mint: &Pubkey,
+) -> Result<u64> {
+    let (address, _) =
+        Pubkey::find_program_address(&[b"partner_fee_reserve", token_mint.as_ref()], &crate::ID);
+    if *partner_fee_reserve.key != address {
+        return Err(NeptuneError::InvalidPartnerFeeReserve.into());
+    }
+    if partner_fee_reserve.data_is_empty() {
+        return Ok(0);
+    }
+    if partner_fee_reserve.owner != &crate::ID {
+        return Err(ErrorCode::AccountOwnedByWrongProgram.into());
+    }
+    let data = partner_fee_reserve.try_borrow_data()?;
+    Ok(PartnerFeeReserve::try_deserialize(&mut &data[..])?.fees_due)
+}
@@ -8,6 +8,7 @@ import * as path from 'path';
 import * as  solanaWeb3 from '@solana/web3.js' ;
 import { TOKEN_PROGRAM_ID, createMint, ASSOCIATED_TOKEN_PROGRAM_ID, getAssociatedTokenAddress,getAssociatedTokenAddressSync, getOrCreateAssociatedTokenAccount, mintTo } from '@solana/spl-token';
 import TransactionFactory from '@project-serum/anchor/dist/cjs/program/namespace/transaction';
+import { expect } from 'chai';
 
 // Function to load a keypair from a JSON file
 function loadKeypairFromFile(filePath) {
@@ -43,6 +44,39 @@ async function requestAirdrop(connection, publicKey, lamports) {
 function sleep(ms) {
   return new Promise(resolve => setTimeout(resolve, ms));
 }
+// Metaplex Token Metadata program, loaded into the local validator from tests/fixtures by Anchor.toml
+const TOKEN_METADATA_PROGRAM_ID = new PublicKey('metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s');
+// Weights are 1e18 fixed-point values, 1e16 being 1%
+function weight(percent) {
+  return new anchor.BN(percent).mul(new anchor.BN(10).pow(new anchor.BN(16)));
+}
+
+// Last pool account seed: the pool's index among its creator's pools, as a little-endian u64
+function poolIndexSeed(index) {
+  return new anchor.BN(index).toArrayLike(Buffer, 'le', 8);
+}
+
+// Launch partner fee reserve of a mint, the default public key standing for SOL
+function partnerFeeReservePda(programId, mint) {
+  return PublicKey.findProgramAddressSync([Buffer.from('partner_fee_reserve'), mint.toBuffer()], programId)[0];
+}
+
+// Runs a call that must fail and checks it failed with the given NeptuneError variant
+async function expectNeptuneError(call, code) {
+  let error;
+  try {
+    await call;
+  } catch (err) {
+    error = err;
+  }
+  expect(error, `expected the call to fail with ${code}`).to.not.be.undefined;
+  const anchorError = error instanceof anchor.AnchorError ? error : anchor.AnchorError.parse(error.logs);
+  expect(anchorError?.error.errorCode.code).to.equal(code);
+}
+
+async function tokenBalance(connection, ata) {
+  return new anchor.BN((await connection.getTokenAccountBalance(ata)).value.amount);
+}
 
 describe("neptune-lbp", () => {
 
@@ -78,8 +112,20 @@ describe("neptune-lbp", () => {
   let _masterAccountPda
   let _masterInputAta
   let _masterOutputAta
+  let _poolFeeLedgerPda
+  let _solFeeVaultPda
+  let _creatorAccountPda
   let _adminInputAta
   let _adminOutputAta
+  // three-token pool: a project token sold against two assets
+  let _multiTokenMints
+  let _multiPoolAccountPda
+  let _multiBpTokenMint
+  let _multiPoolFeeLedgerPda
+  let _multiWeightSchedulePda
+  let _multiPoolMetadataPda
+  let _multiStartTimestamp
+  let _multiEndTimestamp
 
   const aliceKeyPath = path.join(__dirname, '../wallets/ACQXYUiRwziuoeXNeJyZ8bB2fdkmU7ftaiDLbmEqkSYW.json');
   const bobKeyPath = path.join(__dirname, '../wallets/NeptkHfKK36uWkxTSGuta1gzwnPXjZSkmCsD7NW54ib.json');
@@ -119,25 +165,154 @@ describe("neptune-lbp", () => {
       systemProgram: SystemProgram.programId,
     }).rpc();
     console.log("Your transaction signature", tx);
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.admin.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    expect(masterAccount.feeCollector.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    expect(masterAccount.paused).to.equal(false);
   });
   it("Admin can set fees!", async () => {
-    let swap_fee = new anchor.BN(2);
-    let flat_rate= new anchor.BN(3);
+    let swap_fee = 200; // bps
+    let flat_rate = 300; // bps
     const tx = await program.methods.setFeePercentage(swap_fee,flat_rate).accounts({
       masterAccount: masterAccountPda,
       admin: provider.wallet.publicKey,
       systemProgram: SystemProgram.programId,
     }).rpc();
     console.log("Your transaction signature", tx);
+    // queued behind the fee change delay, not applied yet
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.pendingSwapFeeBps).to.equal(swap_fee);
+    expect(masterAccount.pendingFlatRateBps).to.equal(flat_rate);
+    expect(masterAccount.pendingFeeEffectiveTimestamp.toNumber()).to.be.greaterThan(Date.now() / 1000);
+  });
+  it("Queued fees cannot be applied before the delay!", async () => {
+    await expectNeptuneError(
+      program.methods.applyFeePercentage().accounts({
+        masterAccount: masterAccountPda,
+        user: provider.wallet.publicKey,
+      }).rpc(),
+      "FeeChangeTimelocked"
+    );
+  });
+  it("Admin can pause and resume the whole protocol!", async () => {
+    const txPause = await program.methods.setProtocolPause(true).accounts({
+      masterAccount: masterAccountPda,
+      authority: provider.wallet.publicKey,
+    }).rpc();
+    console.log("Your transaction signature", txPause);
+    expect((await program.account.masterAccount.fetch(masterAccountPda)).paused).to.equal(true);
+    const txResume = await program.methods.setProtocolPause(false).accounts({
+      masterAccount: masterAccountPda,
+      authority: provider.wallet.publicKey,
+    }).rpc();
+    console.log("Your transaction signature", txResume);
+    expect((await program.account.masterAccount.fetch(masterAccountPda)).paused).to.equal(false);
+  });
+  it("Admin can set and remove a partner fee tier!", async () => {
+    const partner = Keypair.generate().publicKey;
+    const [feeTierPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("fee_tier"), partner.toBuffer()],
+      programId
+    );
+    const txSet = await program.methods.setFeeTier(partner, 100, 150).accounts({
+      masterAccount: masterAccountPda,
+      feeTier: feeTierPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txSet);
+    const feeTier = await program.account.feeTier.fetch(feeTierPda);
+    expect(feeTier.creator.toBase58()).to.equal(partner.toBase58());
+    expect(feeTier.swapFeeBps).to.equal(100);
+    expect(feeTier.flatRateBps).to.equal(150);
+    const txRemove = await program.methods.removeFeeTier().accounts({
+      masterAccount: masterAccountPda,
+      feeTier: feeTierPda,
+      admin: provider.wallet.publicKey,
+    }).rpc();
+    console.log("Your transaction signature", txRemove);
+    expect(await program.account.feeTier.fetchNullable(feeTierPda)).to.be.null;
   });
-  it("Admin can revoke his privileges for somebody else!", async () => {
+  it("Admin can set the pool creation fee!", async () => {
+    const txSet = await program.methods.setPoolCreationFee(new anchor.BN(LAMPORTS_PER_SOL / 10), PublicKey.default).accounts({
+      masterAccount: masterAccountPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txSet);
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.poolCreationFee.toNumber()).to.equal(LAMPORTS_PER_SOL / 10);
+    expect(masterAccount.poolCreationFeeMint.toBase58()).to.equal(PublicKey.default.toBase58());
+    // back to free so the pool tests below are not charged
+    const txReset = await program.methods.setPoolCreationFee(new anchor.BN(0), PublicKey.default).accounts({
+      masterAccount: masterAccountPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txReset);
+    expect((await program.account.masterAccount.fetch(masterAccountPda)).poolCreationFee.toNumber()).to.equal(0);
+  });
+  it("Admin can approve and revoke pool creators!", async () => {
+    const creator = Keypair.generate().publicKey;
+    const [approvedCreatorPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("approved_creator"), creator.toBuffer()],
+      programId
+    );
+    const txApprove = await program.methods.approveCreator(creator).accounts({
+      masterAccount: masterAccountPda,
+      approvedCreator: approvedCreatorPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txApprove);
+    const approvedCreator = await program.account.approvedCreator.fetch(approvedCreatorPda);
+    expect(approvedCreator.creator.toBase58()).to.equal(creator.toBase58());
+    const txRevoke = await program.methods.revokeCreator().accounts({
+      masterAccount: masterAccountPda,
+      approvedCreator: approvedCreatorPda,
+      admin: provider.wallet.publicKey,
+    }).rpc();
+    console.log("Your transaction signature", txRevoke);
+    expect(await program.account.approvedCreator.fetchNullable(approvedCreatorPda)).to.be.null;
+  });
+  it("Admin can split fees between several recipients!", async () => {
+    const insuranceFund = Keypair.generate().publicKey;
+    const tx = await program.methods.setFeeDistribution(
+      [provider.wallet.publicKey, insuranceFund],
+      [8000, 2000]
+    ).accounts({
+      masterAccount: masterAccountPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", tx);
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.feeRecipientCount).to.equal(2);
+    expect(masterAccount.feeRecipients.slice(0, 2).map((recipient) => recipient.toBase58())).to.deep.equal(
+      [provider.wallet.publicKey.toBase58(), insuranceFund.toBase58()]
+    );
+    expect(masterAccount.feeRecipientSharesBps.slice(0, 2)).to.deep.equal([8000, 2000]);
+  });
+  it("Admin can propose somebody else as admin!", async () => {
     let admin = provider.wallet.publicKey;
-    const tx = await program.methods.setAdmin(admin).accounts({
+    const tx = await program.methods.proposeAdmin(admin).accounts({
       masterAccount: masterAccountPda,
       admin: provider.wallet.publicKey,
       systemProgram: SystemProgram.programId,
     }).rpc();
     console.log("Your transaction signature", tx);
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.pendingAdmin.toBase58()).to.equal(admin.toBase58());
+  });
+  it("Proposed admin can accept the admin role!", async () => {
+    const tx = await program.methods.acceptAdmin().accounts({
+      masterAccount: masterAccountPda,
+      pendingAdmin: provider.wallet.publicKey,
+    }).rpc();
+    console.log("Your transaction signature", tx);
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.admin.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    expect(masterAccount.pendingAdmin.toBase58()).to.equal(PublicKey.default.toBase58());
   });
   it("Admin can change the address that can extract the fees from treasury!", async () => {
     let admin = provider.wallet.publicKey;
@@ -147,6 +322,8 @@ describe("neptune-lbp", () => {
       systemProgram: SystemProgram.programId,
     }).rpc();
     console.log("Your transaction signature", tx);
+    const masterAccount = await program.account.masterAccount.fetch(masterAccountPda);
+    expect(masterAccount.feeCollector.toBase58()).to.equal(admin.toBase58());
   });
   it("Verify invariant calculation", async () => {
 
@@ -204,11 +381,27 @@ describe("neptune-lbp", () => {
     );
     _outputTokenMint = outputTokenMint
     // Derive the address and bump seed for the Pool account PDA
+    const [creatorAccountPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('creator_account'), provider.wallet.publicKey.toBuffer()],
+      program.programId
+    );
+    _creatorAccountPda = creatorAccountPda
+    // first pool of the wallet
     const [poolAccountPda, poolAccountBump] = await anchor.web3.PublicKey.findProgramAddress(
-      [Buffer.from('pool_account'), provider.wallet.publicKey.toBuffer(), inputTokenMint.toBuffer()],
+      [Buffer.from('pool_account'), provider.wallet.publicKey.toBuffer(), inputTokenMint.toBuffer(), poolIndexSeed(1)],
       program.programId
     );
     _poolAccountPda = poolAccountPda
+    const [poolFeeLedgerPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_fee_ledger'), poolAccountPda.toBuffer()],
+      program.programId
+    );
+    _poolFeeLedgerPda = poolFeeLedgerPda
+    const [solFeeVaultPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('sol_fee_vault')],
+      program.programId
+    );
+    _solFeeVaultPda = solFeeVaultPda
     // Derive the address and bump seed for the Pool account PDA
     const [masterAccountPda, masterAccountBump] = await anchor.web3.PublicKey.findProgramAddress(
       [Buffer.from('master_account')],
@@ -246,9 +439,10 @@ describe("neptune-lbp", () => {
       accountType: 0, // Example value
       startTimestamp: new anchor.BN(Date.now() / 1000+1), // Current timestamp
       endTimestamp: new anchor.BN(Date.now() / 1000 + 11), // One hour later
-      startWeights: [new anchor.BN(90), new anchor.BN(10)], // Example weights
-      endWeights: [new anchor.BN(10), new anchor.BN(90)], // Example weights
-      isSol: false
+      startWeights: [weight(90), weight(10)], // Example weights
+      endWeights: [weight(10), weight(90)], // Example weights
+      isSol: false,
+      weightCurve: { logarithmic: {} }, // weights drop fast early, then flatten out
     };
     if (params.isSol) {
 
@@ -299,10 +493,18 @@ describe("neptune-lbp", () => {
         inputTokenMint: inputTokenMint,
         outputTokenMint: outputTokenMint,
         masterAccount: masterAccountPda,
+        feeTier: null, // no partner fee tier for this creator
+        approvedCreator: null, // creator allowlist is disabled
+        creationFeeMint: null, // no SPL creation fee
+        userCreationFeeAta: null,
+        masterAccountCreationFeeAta: null,
+        solFeeVault: solFeeVaultPda,
         masterAccountInputFeeAta:masterInputTokenAta.address,
         masterAccountOutputFeeAta:masterOutputTokenAta.address,
+        creatorAccount: creatorAccountPda,
         poolAccount: poolAccountPda, // Use the derived PDA
        bpTokenMint: bpTokenMint, // Assume this is correctly derived elsewhere
+        poolFeeLedger: poolFeeLedgerPda,
         associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -362,6 +564,7 @@ describe("neptune-lbp", () => {
     const initPoolFundsParams = {
       balances: [new anchor.BN(900000000000), new anchor.BN(100000000000)], // Example initial amounts for input and output tokens
       normalizedWeights: [new anchor.BN(90), new anchor.BN(10)], // Example normalized weights
+      additionalBalances: [], // two-token pool
     };
 
     const bpTokenAta = await getOrCreateAssociatedTokenAccount(
@@ -390,6 +593,7 @@ describe("neptune-lbp", () => {
     instructionTwo = await program.methods.initializePoolFunds(initPoolFundsParams)
       .accounts({
         user: provider.wallet.publicKey,
+        masterAccount: masterAccountPda,
         inputTokenMint: inputTokenMint,
         outputTokenMint: outputTokenMint,
         bpTokenMint: bpTokenMint,
@@ -422,6 +626,12 @@ describe("neptune-lbp", () => {
     console.log(`Input Token ${poolInputTokenBalanceAfter.value.amount}`);
     console.log(`Output Token ${poolOutputTokenBalanceAfter.value.amount}`);
     console.log(`User Bp Token ${ownerBpTokenBalanceAfter.value.amount}`);
+    expect(poolInputTokenBalanceAfter.value.amount).to.equal("900000000000");
+    expect(poolOutputTokenBalanceAfter.value.amount).to.equal("100000000000");
+    expect(new anchor.BN(ownerBpTokenBalanceAfter.value.amount).gtn(0)).to.equal(true);
+    const poolAccount = await program.account.poolAccount.fetch(poolAccountPda);
+    expect(poolAccount.status).to.deep.equal({ funded: {} });
+    expect(poolAccount.owner.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
 
   
   });
@@ -431,6 +641,7 @@ describe("neptune-lbp", () => {
     const ownerBpTokenBalanceBefore= await provider.connection.getTokenAccountBalance(_ownerBpAta.address);
     const userInputTokenBalanceBefore = await provider.connection.getTokenAccountBalance(_ownerInputAta.address);
     const userOutputTokenBalanceBefore = await provider.connection.getTokenAccountBalance(_ownerOutputAta.address);
+    const invariantBefore = (await program.account.poolAccount.fetch(_poolAccountPda)).invariant;
   
     const amountOuputToken = new anchor.BN(10000000000); // 10 tokens, assuming 9 decimals
    
@@ -439,11 +650,13 @@ describe("neptune-lbp", () => {
     const instruction = await program.methods.joinPool(amountOuputToken)
       .accounts({
         user:wallet.publicKey,
+        masterAccount: masterAccountPda,
         outputTokenMint:_outputTokenMint,
         userOutputAta: _ownerOutputAta.address, // The user's input token ATA
         userBpAta: _ownerBpAta.address, // The user's BP token ATA, needs to be created if it doesn't exist
         inputTokenMint: _inputTokenMint,
         poolAccount: _poolAccountPda, // The pool account PDA
+        weightSchedule: null, // the pool follows its start to end weights
         poolInputAta: _poolInputAta, // The pool's input token ATA
         poolOutputAta: _poolOutputAta, // The pool's input token ATA
         poolBpAta: _poolBpAta.address, // The pool's BP token ATA
@@ -474,15 +687,71 @@ describe("neptune-lbp", () => {
     console.log(`Owners's ouput token balance after joining the pool: ${userOutputTokenBalanceAfter.value.amount}`); 
     console.log(`Owner's bp token balance before joining the pool: ${ownerBpTokenBalanceBefore.value.amount}`);
     console.log(`Owners's bp token balance after joining the pool: ${ownerBpTokenBalanceAfter.value.amount}`);
-   
-    // Add assertions to verify that the balances have updated as expected
-    // For example, using chai:
-    // expect(new anchor.BN(userInputTokenBalanceAfter.value.amount)).to.be.lessThan(new anchor.BN(1000000000)); // User should have less input tokens now
-    // expect(new anchor.BN(userBpTokenBalanceAfter.value.amount)).to.be.greaterThan(new anchor.BN(0)); // User should have received BP tokens
+
+    // the owner paid exactly the output tokens it joined with and got BP tokens for them
+    expect(
+      new anchor.BN(userOutputTokenBalanceBefore.value.amount).sub(new anchor.BN(userOutputTokenBalanceAfter.value.amount)).toString()
+    ).to.equal(amountOuputToken.toString());
+    expect(userInputTokenBalanceAfter.value.amount).to.equal(userInputTokenBalanceBefore.value.amount);
+    expect(
+      new anchor.BN(ownerBpTokenBalanceAfter.value.amount).gt(new anchor.BN(ownerBpTokenBalanceBefore.value.amount))
+    ).to.equal(true);
+    expect((await tokenBalance(provider.connection, _poolOutputAta)).toString()).to.equal("110000000000");
+    // the stored invariant is recomputed from the grown balances
+    const invariantAfter = (await program.account.poolAccount.fetch(_poolAccountPda)).invariant;
+    expect(invariantAfter.gt(invariantBefore)).to.equal(true);
 
   });
   
   // Allow a user to  swap a token
+  it("Admin can share swap fees with registered referrers!", async () => {
+    const txShare = await program.methods.setReferralFeeShare(2000).accounts({
+      masterAccount: _masterAccountPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txShare);
+    expect((await program.account.masterAccount.fetch(_masterAccountPda)).referralFeeShareBps).to.equal(2000);
+    const [referralAccountPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("referral"), provider.wallet.publicKey.toBuffer(), _inputTokenMint.toBuffer()],
+      programId
+    );
+    const txRegister = await program.methods.registerReferrer().accounts({
+      referrer: provider.wallet.publicKey,
+      tokenMint: _inputTokenMint,
+      referralAccount: referralAccountPda,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txRegister);
+    const referralAccount = await program.account.referralAccount.fetch(referralAccountPda);
+    expect(referralAccount.referrer.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    expect(referralAccount.tokenMint.toBase58()).to.equal(_inputTokenMint.toBase58());
+    expect(referralAccount.cumulativeVolume.toNumber()).to.equal(0);
+  });
+  it('Owner can pause and resume the pool', async () => {
+    const txPause = await program.methods.pausePool().accounts({
+      owner: provider.wallet.publicKey,
+      inputTokenMint: _inputTokenMint,
+      poolAccount: _poolAccountPda,
+    }).rpc();
+    console.log("Your transaction signature", txPause);
+    const pausedStatus = await program.methods.getPoolStatus().accounts({
+      poolAccount: _poolAccountPda,
+    }).view();
+    console.log("Pool status while paused", pausedStatus);
+    expect(pausedStatus).to.deep.equal({ paused: {} });
+    const txResume = await program.methods.unpausePool().accounts({
+      user: provider.wallet.publicKey,
+      inputTokenMint: _inputTokenMint,
+      poolAccount: _poolAccountPda,
+    }).rpc();
+    console.log("Your transaction signature", txResume);
+    const resumedStatus = await program.methods.getPoolStatus().accounts({
+      poolAccount: _poolAccountPda,
+    }).view();
+    console.log("Pool status after resuming", resumedStatus);
+    expect(resumedStatus).to.deep.equal({ live: {} });
+  });
   it('Allows a user to Swap', async () => {
     await sleep(3000);
     const aliceOutputTokenAta = await createAtaForUser(_outputTokenMint, aliceKeyPair, provider);
@@ -549,11 +818,15 @@ describe("neptune-lbp", () => {
         userBpAta: _aliceBpAta.address, // The user's BP token ATA, needs to be created if it doesn't exist
         inputTokenMint: _inputTokenMint,
         poolAccount: _poolAccountPda, // The pool account PDA
+        weightSchedule: null, // the pool follows its start to end weights
         poolInputAta: _poolInputAta, // The pool's input token ATA
         poolOutputAta: _poolOutputAta, // The pool's input token ATA
         poolBpAta: _poolBpAta.address, // The pool's BP token ATA
         bpTokenMint: _bpTokenMint, // The BP token mint,
         feeCollectorInputAta: _masterInputAta.address, 
+        referralAccount: null, // no referrer for this swap
+        referrerInputAta: null,
+        poolFeeLedger: _poolFeeLedgerPda,
         associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -579,7 +852,17 @@ describe("neptune-lbp", () => {
     console.log(`Swapper's input token balance after joining the pool: ${userInputTokenBalanceAfter.value.amount}`);
     console.log(`Swapper's ouput token balance before joining the pool: ${userOutputTokenBalanceBefore.value.amount}`);
     console.log(`Swapper's ouput token balance after joining the pool: ${userOutputTokenBalanceAfter.value.amount}`); 
-    
+
+    // Alice paid exactly the output tokens she swapped and received project tokens
+    expect(
+      new anchor.BN(userOutputTokenBalanceBefore.value.amount).sub(new anchor.BN(userOutputTokenBalanceAfter.value.amount)).toString()
+    ).to.equal(amountOuputToken.toString());
+    expect(
+      new anchor.BN(userInputTokenBalanceAfter.value.amount).gt(new anchor.BN(userInputTokenBalanceBefore.value.amount))
+    ).to.equal(true);
+    const ledger = await program.account.poolFeeLedger.fetch(_poolFeeLedgerPda);
+    expect(ledger.swapFees[0].gtn(0)).to.equal(true);
+    expect(ledger.referralFees[0].toNumber()).to.equal(0);
   });
 
   it('Allows the owner to redeem his token ', async () => {
@@ -587,11 +870,14 @@ describe("neptune-lbp", () => {
     const ownerBpTokenBalanceBefore= await provider.connection.getTokenAccountBalance(_ownerBpAta.address);
     const poolInputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_poolInputAta);
     const poolOutputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_poolOutputAta);
+    const ownerInputTokenBalanceBefore = await tokenBalance(provider.connection, _ownerInputAta.address);
+    const ownerOutputTokenBalanceBefore = await tokenBalance(provider.connection, _ownerOutputAta.address);
    
     console.log(`Owner Bp Token Before${ownerBpTokenBalanceBefore.value.amount}`);
     console.log(`Pool balance before : in ${poolInputTokenBalanceAfter.value.amount} out ${poolOutputTokenBalanceAfter.value.amount}`);
 
-    const amountBpToken = new anchor.BN(ownerBpTokenBalanceBefore.value.amount); // 500 tokens, assuming 9 decimals
+    // half of the BP tokens, the owner withdraws the rest with ownerExit
+    const amountBpToken = new anchor.BN(ownerBpTokenBalanceBefore.value.amount).div(new anchor.BN(2));
    /* console.log({
       owner: provider.wallet.publicKey,
       user: aliceKeyPair.publicKey,
@@ -624,6 +910,8 @@ describe("neptune-lbp", () => {
         poolBpAta: _poolBpAta.address, // The pool's BP token ATA
         bpTokenMint: _bpTokenMint, // The BP token mint
         feeCollectorOutputAta: _masterOutputAta.address, 
+        solFeeVault: _solFeeVaultPda,
+        poolFeeLedger: _poolFeeLedgerPda,
         associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
         tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -652,6 +940,65 @@ describe("neptune-lbp", () => {
     
     console.log(`User's BP token balance after joining the pool: ${userBpTokenBalanceAfter.value.amount}`);
 
+    expect(
+      new anchor.BN(ownerBpTokenBalanceBefore.value.amount).sub(new anchor.BN(userBpTokenBalanceAfter.value.amount)).toString()
+    ).to.equal(amountBpToken.toString());
+    expect(new anchor.BN(userInputTokenBalanceAfter.value.amount).gt(ownerInputTokenBalanceBefore)).to.equal(true);
+    expect(new anchor.BN(userOutputTokenBalanceAfter.value.amount).gt(ownerOutputTokenBalanceBefore)).to.equal(true);
+  });
+  it('Allows the owner to exit the pool', async () => {
+    const poolOutputTokenBalanceBefore = await tokenBalance(provider.connection, _poolOutputAta);
+    const ownerOutputTokenBalanceBefore = await tokenBalance(provider.connection, _ownerOutputAta.address);
+    const masterOutputTokenBalanceBefore = await tokenBalance(provider.connection, _masterOutputAta.address);
+    const ledgerBefore = await program.account.poolFeeLedger.fetch(_poolFeeLedgerPda);
+    const tx = await program.methods.ownerExit(new anchor.BN(0), new anchor.BN(0)).accounts({
+      owner: provider.wallet.publicKey,
+      masterAccount: _masterAccountPda,
+      inputTokenMint: _inputTokenMint,
+      outputTokenMint: _outputTokenMint,
+      poolAccount: _poolAccountPda,
+      bpTokenMint: _bpTokenMint,
+      userBpAta: _ownerBpAta.address,
+      userInputAta: _ownerInputAta.address,
+      userOutputAta: _ownerOutputAta.address,
+      poolInputAta: _poolInputAta,
+      poolOutputAta: _poolOutputAta,
+      feeCollectorOutputAta: _masterOutputAta.address,
+      solFeeVault: _solFeeVaultPda,
+      poolFeeLedger: _poolFeeLedgerPda,
+      associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).preInstructions([modifyComputeUnits]).rpc();
+    console.log("Your transaction signature", tx);
+    const poolInputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_poolInputAta);
+    const poolOutputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_poolOutputAta);
+    console.log(`Pool balance after the owner exit: in ${poolInputTokenBalanceAfter.value.amount} out ${poolOutputTokenBalanceAfter.value.amount}`);
+
+    expect((await provider.connection.getTokenAccountBalance(_ownerBpAta.address)).value.amount).to.equal("0");
+    const poolAccount = await program.account.poolAccount.fetch(_poolAccountPda);
+    expect(poolAccount.status).to.deep.equal({ finalized: {} });
+    // the flat rate is taken once, on what the owner withdraws, and recorded in the ledger
+    const withdrawn = poolOutputTokenBalanceBefore.sub(new anchor.BN(poolOutputTokenBalanceAfter.value.amount));
+    const ownerReceived = (await tokenBalance(provider.connection, _ownerOutputAta.address)).sub(ownerOutputTokenBalanceBefore);
+    const flatRateFee = (await tokenBalance(provider.connection, _masterOutputAta.address)).sub(masterOutputTokenBalanceBefore);
+    expect(ownerReceived.add(flatRateFee).toString()).to.equal(withdrawn.toString());
+    expect(flatRateFee.toString()).to.equal(withdrawn.muln(poolAccount.flatRateBps).divn(10000).toString());
+    const ledger = await program.account.poolFeeLedger.fetch(_poolFeeLedgerPda);
+    expect(ledger.flatRateFees[1].sub(ledgerBefore.flatRateFees[1]).toString()).to.equal(flatRateFee.toString());
+  });
+  it("Pool fee ledger reports the fees of the pool!", async () => {
+    const ledger = await program.methods.getPoolFeeLedger().accounts({
+      poolAccount: _poolAccountPda,
+      poolFeeLedger: _poolFeeLedgerPda,
+    }).view();
+    console.log(`Swap fees: ${ledger.swapFees.map((fee) => fee.toString())}`);
+    console.log(`Flat rate fees: ${ledger.flatRateFees.map((fee) => fee.toString())}`);
+    const storedLedger = await program.account.poolFeeLedger.fetch(_poolFeeLedgerPda);
+    expect(ledger.swapFees.map((fee) => fee.toString())).to.deep.equal(storedLedger.swapFees.map((fee) => fee.toString()));
+    expect(ledger.flatRateFees.map((fee) => fee.toString())).to.deep.equal(storedLedger.flatRateFees.map((fee) => fee.toString()));
+    expect(ledger.swapFees[0].gtn(0)).to.equal(true);
+    expect(ledger.flatRateFees[1].gtn(0)).to.equal(true);
   });
   it("feeCollector Can collect inToken fees!", async () => {
     let admin = provider.wallet.publicKey;
@@ -663,6 +1010,7 @@ describe("neptune-lbp", () => {
       feeCollectorTokenAta:_ownerInputAta.address,
       masterAccountTokenAta:_masterInputAta.address,
       tokenMint:_inputTokenMint,
+      partnerFeeReserve: partnerFeeReservePda(program.programId, _inputTokenMint),
       associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
       tokenProgram:  anchor.utils.token.TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId,
@@ -676,7 +1024,10 @@ describe("neptune-lbp", () => {
     console.log(`Master fee account Before${masterInputTokenBalanceBefore.value.amount}`);
     console.log(`Fee collector balance before ${collectorInputTokenBalanceBefore.value.amount}`);
     console.log(`Fee collector balance after ${collectorInputTokenBalanceAfter.value.amount}`);
-    
+    expect(masterInputTokenBalanceAfter.value.amount).to.equal("0");
+    expect(
+      new anchor.BN(collectorInputTokenBalanceAfter.value.amount).sub(new anchor.BN(collectorInputTokenBalanceBefore.value.amount)).toString()
+    ).to.equal(masterInputTokenBalanceBefore.value.amount);
   });
   it("feeCollector Can collect outToken fees!", async () => {
     let admin = provider.wallet.publicKey;
@@ -688,6 +1039,7 @@ describe("neptune-lbp", () => {
       feeCollectorTokenAta:_ownerOutputAta.address,
       masterAccountTokenAta:_masterOutputAta.address,
       tokenMint:_outputTokenMint,
+      partnerFeeReserve: partnerFeeReservePda(program.programId, _outputTokenMint),
       associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
       tokenProgram:  anchor.utils.token.TOKEN_PROGRAM_ID,
       systemProgram: SystemProgram.programId,
@@ -701,7 +1053,355 @@ describe("neptune-lbp", () => {
     console.log(`Master fee account Before${masterInputTokenBalanceBefore.value.amount}`);
     console.log(`Fee collector balance before ${collectorInputTokenBalanceBefore.value.amount}`);
     console.log(`Fee collector balance after ${collectorInputTokenBalanceAfter.value.amount}`);
-    
+    expect(masterInputTokenBalanceAfter.value.amount).to.equal("0");
+    expect(
+      new anchor.BN(collectorInputTokenBalanceAfter.value.amount).sub(new anchor.BN(collectorInputTokenBalanceBefore.value.amount)).toString()
+    ).to.equal(masterInputTokenBalanceBefore.value.amount);
+  });
+
+  it("feeCollector Can collect all fees at once!", async () => {
+    const tx = await program.methods.collectAllFees().accounts({
+      masterAccount: _masterAccountPda,
+      solFeeVault: _solFeeVaultPda,
+      solPartnerFeeReserve: partnerFeeReservePda(program.programId, PublicKey.default),
+      tokenProgram:  anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).remainingAccounts([
+      { pubkey: _masterInputAta.address, isWritable: true, isSigner: false },
+      { pubkey: _ownerInputAta.address, isWritable: true, isSigner: false },
+      { pubkey: partnerFeeReservePda(program.programId, _inputTokenMint), isWritable: false, isSigner: false },
+      { pubkey: _masterOutputAta.address, isWritable: true, isSigner: false },
+      { pubkey: _ownerOutputAta.address, isWritable: true, isSigner: false },
+      { pubkey: partnerFeeReservePda(program.programId, _outputTokenMint), isWritable: false, isSigner: false },
+    ]).rpc();
+    console.log("Your transaction signature", tx);
+    const collectorInputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_ownerInputAta.address);
+    const collectorOutputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_ownerOutputAta.address);
+    console.log(`Fee collector input balance after ${collectorInputTokenBalanceAfter.value.amount}`);
+    console.log(`Fee collector output balance after ${collectorOutputTokenBalanceAfter.value.amount}`);
+    expect((await provider.connection.getTokenAccountBalance(_masterInputAta.address)).value.amount).to.equal("0");
+    expect((await provider.connection.getTokenAccountBalance(_masterOutputAta.address)).value.amount).to.equal("0");
+  });
+
+  it('Owner can create a pool with a third token', async () => {
+    const mints = [];
+    for (let i = 0; i < 3; i++) {
+      mints.push(await createMint(provider.connection, wallet.payer, provider.wallet.publicKey, null, 9));
+    }
+    _multiTokenMints = mints
+    // second pool of the wallet
+    const [poolAccountPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_account'), provider.wallet.publicKey.toBuffer(), mints[0].toBuffer(), poolIndexSeed(2)],
+      program.programId
+    );
+    _multiPoolAccountPda = poolAccountPda
+    const [bpTokenMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from('bp_token_mint'), poolAccountPda.toBuffer()],
+      program.programId
+    );
+    _multiBpTokenMint = bpTokenMint
+    const [poolFeeLedgerPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_fee_ledger'), poolAccountPda.toBuffer()],
+      program.programId
+    );
+    _multiPoolFeeLedgerPda = poolFeeLedgerPda
+    const [weightSchedulePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('weight_schedule'), poolAccountPda.toBuffer()],
+      program.programId
+    );
+    _multiWeightSchedulePda = weightSchedulePda
+    const [poolMetadataPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_metadata'), poolAccountPda.toBuffer()],
+      program.programId
+    );
+    _multiPoolMetadataPda = poolMetadataPda
+    // leaves time to set the weight schedule and the metadata before the sale starts
+    _multiStartTimestamp = Math.floor(Date.now() / 1000) + 10
+    _multiEndTimestamp = Math.floor(Date.now() / 1000) + 35
+    const params = {
+      accountType: 0,
+      startTimestamp: new anchor.BN(_multiStartTimestamp),
+      endTimestamp: new anchor.BN(_multiEndTimestamp),
+      startWeights: [weight(90), weight(10)],
+      endWeights: [weight(10), weight(90)],
+      isSol: false,
+      weightCurve: { linear: {} },
+    };
+    const txInit = await program.methods.initializePool(params).accounts({
+      user: provider.wallet.publicKey,
+      inputTokenMint: mints[0],
+      outputTokenMint: mints[1],
+      masterAccount: _masterAccountPda,
+      feeTier: null,
+      approvedCreator: null,
+      creationFeeMint: null,
+      userCreationFeeAta: null,
+      masterAccountCreationFeeAta: null,
+      solFeeVault: _solFeeVaultPda,
+      masterAccountInputFeeAta: getAssociatedTokenAddressSync(mints[0], _masterAccountPda, true),
+      masterAccountOutputFeeAta: getAssociatedTokenAddressSync(mints[1], _masterAccountPda, true),
+      creatorAccount: _creatorAccountPda,
+      poolAccount: poolAccountPda,
+      bpTokenMint: bpTokenMint,
+      poolFeeLedger: poolFeeLedgerPda,
+      associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txInit);
+
+    // 80/10/10 to start, 20/40/40 at the end
+    const tx = await program.methods.addPoolToken([weight(80), weight(10), weight(10)], [weight(20), weight(40), weight(40)]).accounts({
+      user: provider.wallet.publicKey,
+      masterAccount: _masterAccountPda,
+      inputTokenMint: mints[0],
+      poolAccount: poolAccountPda,
+      tokenMint: mints[2],
+      poolTokenAta: getAssociatedTokenAddressSync(mints[2], poolAccountPda, true),
+      masterAccountFeeAta: getAssociatedTokenAddressSync(mints[2], _masterAccountPda, true),
+      poolFeeLedger: poolFeeLedgerPda,
+      associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", tx);
+    const poolAccount = await program.account.poolAccount.fetch(poolAccountPda);
+    expect(poolAccount.tokenCount).to.equal(3);
+    expect(poolAccount.poolIndex.toNumber()).to.equal(2);
+    expect(poolAccount.startWeights.slice(0, 3).map((w) => w.toString())).to.deep.equal([80, 10, 10].map((p) => weight(p).toString()));
+    expect(poolAccount.endWeights.slice(0, 3).map((w) => w.toString())).to.deep.equal([20, 40, 40].map((p) => weight(p).toString()));
+  });
+  it('Owner can set a multi-waypoint weight schedule', async () => {
+    // fast drop over the first 5 seconds, then a long flat tail
+    const waypoints = [
+      { timestamp: new anchor.BN(_multiStartTimestamp), weights: [80, 10, 10, 0, 0, 0, 0, 0].map(weight) },
+      { timestamp: new anchor.BN(_multiStartTimestamp + 5), weights: [30, 35, 35, 0, 0, 0, 0, 0].map(weight) },
+      { timestamp: new anchor.BN(_multiEndTimestamp), weights: [20, 40, 40, 0, 0, 0, 0, 0].map(weight) },
+    ];
+    const tx = await program.methods.setWeightSchedule(waypoints).accounts({
+      user: provider.wallet.publicKey,
+      inputTokenMint: _multiTokenMints[0],
+      poolAccount: _multiPoolAccountPda,
+      weightSchedule: _multiWeightSchedulePda,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", tx);
+    const weightSchedule = await program.account.weightSchedule.fetch(_multiWeightSchedulePda);
+    expect(weightSchedule.waypointCount).to.equal(3);
+    expect(weightSchedule.waypoints[1].timestamp.toNumber()).to.equal(_multiStartTimestamp + 5);
+  });
+  it('Owner can name the pool and its BP token', async () => {
+    const [bpTokenMetadataPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from('metadata'), TOKEN_METADATA_PROGRAM_ID.toBuffer(), _multiBpTokenMint.toBuffer()],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+    const tx = await program.methods.setPoolMetadata(
+      "XYZ public round",
+      "XYZ",
+      "https://example.com/xyz-lbp.json",
+      ["https://x.com/xyz", "https://t.me/xyz"],
+    ).accounts({
+      user: provider.wallet.publicKey,
+      inputTokenMint: _multiTokenMints[0],
+      poolAccount: _multiPoolAccountPda,
+      poolMetadata: _multiPoolMetadataPda,
+      bpTokenMint: _multiBpTokenMint,
+      bpTokenMetadata: bpTokenMetadataPda,
+      tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+      rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+    }).rpc();
+    console.log("Your transaction signature", tx);
+    const poolMetadata = await program.account.poolMetadata.fetch(_multiPoolMetadataPda);
+    expect(poolMetadata.name).to.equal("XYZ public round");
+    expect(poolMetadata.symbol).to.equal("XYZ");
+    expect(poolMetadata.uri).to.equal("https://example.com/xyz-lbp.json");
+    expect(poolMetadata.socialLinks).to.deep.equal(["https://x.com/xyz", "https://t.me/xyz"]);
+  });
+  it('Owner can fund and join a pool with three tokens', async () => {
+    const mints = _multiTokenMints
+    const ownerAtas = [];
+    for (const mint of mints) {
+      const ata = await createAtaForUser(mint, myKeypair, provider);
+      await mintTo(provider.connection, wallet.payer, mint, ata.address, provider.wallet.publicKey, 1000000000000);
+      ownerAtas.push(ata.address);
+    }
+    const ownerBpAta = getAssociatedTokenAddressSync(_multiBpTokenMint, provider.wallet.publicKey);
+    const txFunds = await program.methods.initializePoolFunds({
+      balances: [new anchor.BN(800000000000), new anchor.BN(100000000000)],
+      normalizedWeights: [new anchor.BN(80), new anchor.BN(10)],
+      additionalBalances: [new anchor.BN(100000000000)],
+    }).accounts({
+      user: provider.wallet.publicKey,
+      masterAccount: _masterAccountPda,
+      inputTokenMint: mints[0],
+      outputTokenMint: mints[1],
+      bpTokenMint: _multiBpTokenMint,
+      poolAccount: _multiPoolAccountPda,
+      userInputAta: ownerAtas[0],
+      userOutputAta: ownerAtas[1],
+      userBpAta: ownerBpAta,
+      poolInputAta: getAssociatedTokenAddressSync(mints[0], _multiPoolAccountPda, true),
+      poolOutputAta: getAssociatedTokenAddressSync(mints[1], _multiPoolAccountPda, true),
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).remainingAccounts([
+      { pubkey: ownerAtas[2], isWritable: true, isSigner: false },
+      { pubkey: getAssociatedTokenAddressSync(mints[2], _multiPoolAccountPda, true), isWritable: true, isSigner: false },
+    ]).preInstructions([modifyComputeUnits]).rpc();
+    console.log("Your transaction signature", txFunds);
+
+    // user and pool ATAs of each pool token
+    const remainingAccounts = mints.flatMap((mint, i) => [
+      { pubkey: ownerAtas[i], isWritable: true, isSigner: false },
+      { pubkey: getAssociatedTokenAddressSync(mint, _multiPoolAccountPda, true), isWritable: true, isSigner: false },
+    ]);
+    const tx = await program.methods.joinPoolMulti([new anchor.BN(0), new anchor.BN(10000000000), new anchor.BN(10000000000)]).accounts({
+      user: provider.wallet.publicKey,
+      masterAccount: _masterAccountPda,
+      userBpAta: ownerBpAta,
+      inputTokenMint: mints[0],
+      poolAccount: _multiPoolAccountPda,
+      weightSchedule: _multiWeightSchedulePda,
+      bpTokenMint: _multiBpTokenMint,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+    }).remainingAccounts(remainingAccounts).preInstructions([modifyComputeUnits]).rpc();
+    console.log("Your transaction signature", tx);
+    const ownerBpTokenBalanceAfter = await provider.connection.getTokenAccountBalance(ownerBpAta);
+    console.log(`Owner's bp token balance after joining the pool: ${ownerBpTokenBalanceAfter.value.amount}`);
+    const poolBalances = await Promise.all(
+      mints.map((mint) => provider.connection.getTokenAccountBalance(getAssociatedTokenAddressSync(mint, _multiPoolAccountPda, true)))
+    );
+    expect(poolBalances.map((balance) => balance.value.amount)).to.deep.equal(["800000000000", "110000000000", "110000000000"]);
+    expect(new anchor.BN(ownerBpTokenBalanceAfter.value.amount).gtn(0)).to.equal(true);
+  });
+  it('Allows a user to Swap between any two pool tokens', async () => {
+    await sleep(Math.max(0, _multiStartTimestamp * 1000 - Date.now()) + 1000); // wait for the start of the sale
+    const mints = _multiTokenMints
+    // pays with the third token, receives the project token
+    const userTokenInAta = getAssociatedTokenAddressSync(mints[2], provider.wallet.publicKey);
+    const userTokenOutAta = getAssociatedTokenAddressSync(mints[0], provider.wallet.publicKey);
+    const userTokenInBalanceBefore = await tokenBalance(provider.connection, userTokenInAta);
+    const userTokenOutBalanceBefore = await tokenBalance(provider.connection, userTokenOutAta);
+    const tx = await program.methods.swapTokens(new anchor.BN(1000000000), new anchor.BN(0)).accounts({
+      owner: provider.wallet.publicKey,
+      masterAccount: _masterAccountPda,
+      user: provider.wallet.publicKey,
+      inputTokenMint: mints[0],
+      poolAccount: _multiPoolAccountPda,
+      weightSchedule: _multiWeightSchedulePda,
+      tokenInMint: mints[2],
+      tokenOutMint: mints[0],
+      userTokenInAta: userTokenInAta,
+      userTokenOutAta: userTokenOutAta,
+      poolTokenInAta: getAssociatedTokenAddressSync(mints[2], _multiPoolAccountPda, true),
+      poolTokenOutAta: getAssociatedTokenAddressSync(mints[0], _multiPoolAccountPda, true),
+      feeCollectorOutAta: getAssociatedTokenAddressSync(mints[0], _masterAccountPda, true),
+      poolFeeLedger: _multiPoolFeeLedgerPda,
+      associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).preInstructions([modifyComputeUnits]).rpc();
+    console.log("Your transaction signature", tx);
+    const userTokenOutBalanceAfter = await provider.connection.getTokenAccountBalance(userTokenOutAta);
+    console.log(`User's project token balance after the swap: ${userTokenOutBalanceAfter.value.amount}`);
+    expect(userTokenInBalanceBefore.sub(await tokenBalance(provider.connection, userTokenInAta)).toString()).to.equal("1000000000");
+    expect(new anchor.BN(userTokenOutBalanceAfter.value.amount).gt(userTokenOutBalanceBefore)).to.equal(true);
+    const ledger = await program.account.poolFeeLedger.fetch(_multiPoolFeeLedgerPda);
+    expect(ledger.swapFees[0].gtn(0)).to.equal(true);
+    expect((await program.account.poolAccount.fetch(_multiPoolAccountPda)).hasSwapped).to.equal(true);
+  });
+  it('Owner cannot cancel a pool once it has been traded', async () => {
+    const mints = _multiTokenMints
+    await expectNeptuneError(
+      // pool and owner ATAs of the token added with addPoolToken
+      program.methods.cancelPool().accounts({
+        owner: provider.wallet.publicKey,
+        inputTokenMint: mints[0],
+        outputTokenMint: mints[1],
+        poolAccount: _multiPoolAccountPda,
+        bpTokenMint: _multiBpTokenMint,
+        userBpAta: getAssociatedTokenAddressSync(_multiBpTokenMint, provider.wallet.publicKey),
+        userInputAta: getAssociatedTokenAddressSync(mints[0], provider.wallet.publicKey),
+        userOutputAta: getAssociatedTokenAddressSync(mints[1], provider.wallet.publicKey),
+        poolInputAta: getAssociatedTokenAddressSync(mints[0], _multiPoolAccountPda, true),
+        poolOutputAta: getAssociatedTokenAddressSync(mints[1], _multiPoolAccountPda, true),
+        associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      }).remainingAccounts([
+        { pubkey: getAssociatedTokenAddressSync(mints[2], _multiPoolAccountPda, true), isWritable: true, isSigner: false },
+        { pubkey: getAssociatedTokenAddressSync(mints[2], provider.wallet.publicKey), isWritable: true, isSigner: false },
+      ]).rpc(),
+      "PoolNotCancellable"
+    );
+  });
+  it('Owner cannot ramp the weights faster than the maximum rate', async () => {
+    await expectNeptuneError(
+      // ~20 percentage points within the remaining seconds of the sale
+      program.methods.updateWeightsGradually([weight(10), weight(45), weight(45)], new anchor.BN(0), new anchor.BN(_multiEndTimestamp)).accounts({
+        user: provider.wallet.publicKey,
+        inputTokenMint: _multiTokenMints[0],
+        poolAccount: _multiPoolAccountPda,
+        weightSchedule: _multiWeightSchedulePda,
+      }).rpc(),
+      "WeightChangeTooFast"
+    );
+  });
+  it('Allows a user to exit a pool with three tokens', async () => {
+    await sleep(30000); // wait for the end of the schedule
+    const mints = _multiTokenMints
+    const remainingAccounts = mints.flatMap((mint) => [
+      { pubkey: getAssociatedTokenAddressSync(mint, provider.wallet.publicKey), isWritable: true, isSigner: false },
+      { pubkey: getAssociatedTokenAddressSync(mint, _multiPoolAccountPda, true), isWritable: true, isSigner: false },
+      { pubkey: getAssociatedTokenAddressSync(mint, _masterAccountPda, true), isWritable: true, isSigner: false },
+    ]);
+    const userBpAta = getAssociatedTokenAddressSync(_multiBpTokenMint, provider.wallet.publicKey);
+    const ownerBpTokenBalanceBefore = await tokenBalance(provider.connection, userBpAta);
+    const tx = await program.methods.exitPoolMulti(new anchor.BN(100000000)).accounts({
+      owner: provider.wallet.publicKey,
+      masterAccount: _masterAccountPda,
+      user: provider.wallet.publicKey,
+      userBpAta: getAssociatedTokenAddressSync(_multiBpTokenMint, provider.wallet.publicKey),
+      inputTokenMint: mints[0],
+      poolAccount: _multiPoolAccountPda,
+      bpTokenMint: _multiBpTokenMint,
+      poolFeeLedger: _multiPoolFeeLedgerPda,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    }).remainingAccounts(remainingAccounts).preInstructions([modifyComputeUnits]).rpc();
+    console.log("Your transaction signature", tx);
+    expect(ownerBpTokenBalanceBefore.sub(await tokenBalance(provider.connection, userBpAta)).toString()).to.equal("100000000");
+  });
+  it('Owner cannot close a pool while BP tokens are outstanding', async () => {
+    const mints = _multiTokenMints
+    await expectNeptuneError(
+      // pool and owner ATAs of the token added with addPoolToken
+      program.methods.closePool().accounts({
+        user: provider.wallet.publicKey,
+        inputTokenMint: mints[0],
+        outputTokenMint: mints[1],
+        poolAccount: _multiPoolAccountPda,
+        bpTokenMint: _multiBpTokenMint,
+        userInputAta: getAssociatedTokenAddressSync(mints[0], provider.wallet.publicKey),
+        userOutputAta: getAssociatedTokenAddressSync(mints[1], provider.wallet.publicKey),
+        poolInputAta: getAssociatedTokenAddressSync(mints[0], _multiPoolAccountPda, true),
+        poolOutputAta: getAssociatedTokenAddressSync(mints[1], _multiPoolAccountPda, true),
+        poolBpAta: null,
+        poolFeeLedger: _multiPoolFeeLedgerPda,
+        weightSchedule: _multiWeightSchedulePda,
+        poolMetadata: _multiPoolMetadataPda,
+        associatedTokenProgram: anchor.utils.token.ASSOCIATED_PROGRAM_ID,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      }).remainingAccounts([
+        { pubkey: getAssociatedTokenAddressSync(mints[2], _multiPoolAccountPda, true), isWritable: true, isSigner: false },
+        { pubkey: getAssociatedTokenAddressSync(mints[2], provider.wallet.publicKey), isWritable: true, isSigner: false },
+      ]).rpc(),
+      "PoolNotClosable"
+    );
+    // still there for the remaining BP holders to exit
+    expect(await program.account.poolAccount.fetchNullable(_multiPoolAccountPda)).to.not.be.null;
   });
 
   /*it('Allows a user to redeem the token', async () => {
@@ -753,43 +1453,70 @@ describe("neptune-lbp", () => {
     // expect(new anchor.BN(userInputTokenBalanceAfter.value.amount)).to.be.lessThan(new anchor.BN(1000000000)); // User should have less input tokens now
     // expect(new anchor.BN(userBpTokenBalanceAfter.value.amount)).to.be.greaterThan(new anchor.BN(0)); // User should have received BP tokens
 
-  });
-/*
-  it('Allows the owner to exit the pool', async () => {
-    
-    // get the pool  final balances 
-    const poolInputTokenBalanceBefore = await provider.connection.getTokenAccountBalance(_poolInputAta);
-    const poolOuputTokenBalanceBefore = await provider.connection.getTokenAccountBalance(_poolOutputAta);
-
-
-    // Call join_pool
-    const txJoin = await program.methods.ownerExit(new anchor.BN(poolInputTokenBalanceBefore.value.amount),new anchor.BN(poolOuputTokenBalanceBefore.value.amount))
-      .accounts({
-        owner: provider.wallet.publicKey,
-        userInputAta: _ownerInputAta.address, // The user's input token ATA
-        userOutputAta: _ownerOutputAta.address, // The user's input token ATA
-        inputTokenMint: _inputTokenMint, // used to derive pool account
-        poolAccount: _poolAccountPda, // The pool account PDA
-        poolInputAta: _poolInputAta, // The pool's input token ATA
-        poolOutputAta: _poolOutputAta , // The pool's BP token ATA
-        tokenProgram: TOKEN_PROGRAM_ID,
-      })
-      .rpc();
-    console.log("Transaction signature for joining the pool", txJoin);
-
-     // get the pool  final balances 
-     const poolInputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_poolInputAta);
-     const poolOuputTokenBalanceAfter = await provider.connection.getTokenAccountBalance(_poolOutputAta);
- 
-    console.log(`Pool input balance after owner exit the pool: ${poolInputTokenBalanceAfter.value.amount}`);
-    console.log(`Pool output token after owner exit the pool: ${poolOuputTokenBalanceAfter.value.amount}`);
-
-    // Add assertions to verify that the balances have updated as expected
-    // For example, using chai:
-    // expect(new anchor.BN(userInputTokenBalanceAfter.value.amount)).to.be.lessThan(new anchor.BN(1000000000)); // User should have less input tokens now
-    // expect(new anchor.BN(userBpTokenBalanceAfter.value.amount)).to.be.greaterThan(new anchor.BN(0)); // User should have received BP tokens
-
   });*/
 
 
+  it("Admin can hand the protocol configuration over to a council!", async () => {
+    const [councilPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("admin_council")],
+      programId
+    );
+    const tx = await program.methods.initializeCouncil([provider.wallet.publicKey], 1).accounts({
+      masterAccount: masterAccountPda,
+      council: councilPda,
+      admin: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", tx);
+    expect((await program.account.masterAccount.fetch(masterAccountPda)).council.toBase58()).to.equal(councilPda.toBase58());
+    const council = await program.account.adminCouncil.fetch(councilPda);
+    expect(council.memberCount).to.equal(1);
+    expect(council.threshold).to.equal(1);
+  });
+  it("Admin cannot change the fees directly once a council exists!", async () => {
+    await expectNeptuneError(
+      program.methods.setFeePercentage(100, 100).accounts({
+        masterAccount: masterAccountPda,
+        admin: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      }).rpc(),
+      "CouncilRequired"
+    );
+  });
+  it("Council can change the fee collector through a proposal!", async () => {
+    const [councilPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("admin_council")],
+      programId
+    );
+    const [proposalPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("council_proposal"), councilPda.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      programId
+    );
+    const action = { setFeeCollector: { feeCollector: provider.wallet.publicKey } };
+    const txPropose = await program.methods.createCouncilProposal(action).accounts({
+      council: councilPda,
+      proposal: proposalPda,
+      member: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txPropose);
+    const txExecute = await program.methods.executeCouncilProposal().accounts({
+      masterAccount: masterAccountPda,
+      council: councilPda,
+      proposal: proposalPda,
+      poolAccount: null,
+      feeTier: null,
+      approvedCreator: null,
+      member: provider.wallet.publicKey,
+      systemProgram: SystemProgram.programId,
+    }).rpc();
+    console.log("Your transaction signature", txExecute);
+    const proposal = await program.account.councilProposal.fetch(proposalPda);
+    expect(proposal.executed).to.equal(true);
+    expect(proposal.approvalCount).to.equal(1);
+    expect((await program.account.masterAccount.fetch(masterAccountPda)).feeCollector.toBase58()).to.equal(
+      provider.wallet.publicKey.toBase58()
+    );
+  });
+
 });